@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_state::GameState;
+
+// What a unit with build power did during a timestep.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Activity {
+    Idle,
+    // Walking to a build site, a feature or a move target.
+    Moving,
+    // Building the unit with the given index.
+    Building(usize),
+    // In range of the unit with the given index, but waiting for resources.
+    Stalled(usize),
+    // Reclaiming the feature with the given index.
+    Reclaiming(usize),
+}
+
+
+// Time a unit spent on each kind of activity.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct UnitActivity {
+    pub idle_time: f32,
+    pub moving_time: f32,
+    pub stalled_time: f32,
+    // Time spent building or reclaiming
+    pub productive_time: f32,
+    // Build power multiplied by the time it was put into construction
+    pub build_power_used: f32,
+}
+
+
+impl UnitActivity {
+    pub fn add(&mut self, activity: Activity, dt: f32, build_power_used: f32) {
+        match activity {
+            Activity::Idle => self.idle_time += dt,
+            Activity::Moving => self.moving_time += dt,
+            Activity::Stalled(_) => self.stalled_time += dt,
+            Activity::Building(_) | Activity::Reclaiming(_) => self.productive_time += dt,
+        }
+        self.build_power_used += build_power_used;
+    }
+
+
+    pub fn total_time(&self) -> f32 {
+        self.idle_time + self.moving_time + self.stalled_time + self.productive_time
+    }
+
+
+    // Fraction of the available build power that was used.
+    pub fn utilization(&self, buildpower: f32) -> f32 {
+        let available = buildpower * self.total_time();
+        if available > 0.0 { self.build_power_used / available } else { 0.0 }
+    }
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct UtilizationRow {
+    pub unit_idx: usize,
+    pub name: String,
+    pub buildpower: f32,
+    pub activity: UnitActivity,
+}
+
+
+// How well the builders were kept busy over a simulation.
+#[derive(PartialEq, Clone, Debug)]
+pub struct UtilizationReport {
+    pub rows: Vec<UtilizationRow>,
+}
+
+
+impl UtilizationReport {
+    pub fn from_state(state: &GameState) -> UtilizationReport {
+        let mut rows: Vec<UtilizationRow> = state.activity.iter().map(|(unit_idx, activity)| UtilizationRow {
+            unit_idx: *unit_idx,
+            name: state.units[*unit_idx].def_name.clone(),
+            buildpower: state.units[*unit_idx].buildpower,
+            activity: activity.clone(),
+        }).collect();
+        rows.sort_by_key(|row| row.unit_idx);
+        UtilizationReport { rows }
+    }
+
+
+    // Summed up over all builders.
+    pub fn total(&self) -> UnitActivity {
+        let mut total = UnitActivity::default();
+        for row in &self.rows {
+            total.idle_time += row.activity.idle_time;
+            total.moving_time += row.activity.moving_time;
+            total.stalled_time += row.activity.stalled_time;
+            total.productive_time += row.activity.productive_time;
+            total.build_power_used += row.activity.build_power_used;
+        }
+        total
+    }
+
+
+    // Fraction of the build power of all builders that was used.
+    pub fn utilization(&self) -> f32 {
+        let mut available = 0.0;
+        for row in &self.rows {
+            available += row.buildpower * row.activity.total_time();
+        }
+        if available > 0.0 { self.total().build_power_used / available } else { 0.0 }
+    }
+
+
+    pub fn idle_time_by_name(&self) -> HashMap<String, f32> {
+        let mut idle_times = HashMap::new();
+        for row in &self.rows {
+            *idle_times.entry(row.name.clone()).or_default() += row.activity.idle_time;
+        }
+        idle_times
+    }
+}
+
+
+impl fmt::Display for UtilizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  unit                 idle    moving   stalled   working  utilization")?;
+        for row in &self.rows {
+            let a = &row.activity;
+            writeln!(f, "{:>4} {:<14} {:7.1}s  {:7.1}s  {:7.1}s  {:7.1}s  {:10.0}%",
+                row.unit_idx, row.name, a.idle_time, a.moving_time, a.stalled_time, a.productive_time,
+                100.0 * a.utilization(row.buildpower))?;
+        }
+        let total = self.total();
+        write!(f, "     {:<14} {:7.1}s  {:7.1}s  {:7.1}s  {:7.1}s  {:10.0}%",
+            "total", total.idle_time, total.moving_time, total.stalled_time, total.productive_time, 100.0 * self.utilization())
+    }
+}
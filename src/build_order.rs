@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::game_state::GameState;
+use crate::unit::Unit;
+
+// Simulations run until a point in time, so they only end if every step advances the time.
+pub(crate) fn validate_time_step(time_step: f32) -> Result<(), Box<dyn Error>> {
+    if !time_step.is_finite() || time_step <= 0.0 {
+        return Err(format!("Invalid time step {}.", time_step).into())
+    }
+    Ok(())
+}
+
+
+// Repeated steps are written as "unit x3".
+pub(crate) fn parse_step(step: &str) -> Result<(&str, usize), Box<dyn Error>> {
+    Ok(match step.rsplit_once(char::is_whitespace) {
+        Some((unit, count)) if count.starts_with('x') => {
+            let count: usize = count[1..].parse().map_err(|_| format!("Invalid repetition in '{}'.", step))?;
+            (unit.trim(), count)
+        }
+        _ => (step, 1),
+    })
+}
+
+
+// Units that one builder constructs one after another.
+#[derive(PartialEq, Clone, Debug)]
+pub struct BuildQueue {
+    pub builder: String,
+    pub steps: Vec<String>,
+}
+
+
+// Build queues for all builders, e.g. "commander: mex, mex, wind x3, lab; lab: con x2".
+// Builders are referred to by their catalog name. If several queues name the same kind of builder,
+// each queue is executed by a different unit.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct BuildOrder {
+    pub queues: Vec<BuildQueue>,
+}
+
+
+impl BuildOrder {
+    // Queues are separated by semicolons or new lines. Everything after a '#' is a comment.
+    pub fn parse(text: &str) -> Result<BuildOrder, Box<dyn Error>> {
+        let mut queues = Vec::new();
+        let lines = text.lines().map(|line| line.split('#').next().unwrap_or_default());
+        for queue_str in lines.flat_map(|line| line.split(';')) {
+            if queue_str.trim().is_empty() {
+                continue;
+            }
+            let (builder, steps_str) = queue_str.split_once(':')
+                .ok_or(format!("Expected 'builder: unit, unit, ...' but got '{}'.", queue_str.trim()))?;
+            let builder = builder.trim();
+            if builder.is_empty() {
+                return Err(format!("Missing builder in '{}'.", queue_str.trim()).into())
+            }
+
+            let mut steps = Vec::new();
+            for step in steps_str.split(',').map(str::trim).filter(|step| !step.is_empty()) {
+                let (unit, count) = parse_step(step)?;
+                steps.extend(std::iter::repeat_n(unit.to_string(), count));
+            }
+            queues.push(BuildQueue { builder: builder.to_string(), steps });
+        }
+        Ok(BuildOrder { queues })
+    }
+
+
+    // Make sure that every unit is known and that the builders may build their steps.
+    pub fn validate(&self, catalog: &HashMap<String, Unit>) -> Result<(), Box<dyn Error>> {
+        for queue in &self.queues {
+            let builder = catalog.get(&queue.builder).ok_or(format!("'{}' is not a known unit.", queue.builder))?;
+            for step in &queue.steps {
+                if !catalog.contains_key(step) {
+                    return Err(format!("'{}' is not a known unit.", step).into())
+                }
+                if !builder.build_options.contains(step) {
+                    return Err(format!("'{}' cannot build unit '{}'.", queue.builder, step).into())
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+
+impl fmt::Display for BuildOrder {
+    // Writes the build order in the same format that `parse` reads.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (queue_idx, queue) in self.queues.iter().enumerate() {
+            if queue_idx > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}:", queue.builder)?;
+            let mut i = 0;
+            while i < queue.steps.len() {
+                let count = queue.steps[i..].iter().take_while(|step| **step == queue.steps[i]).count();
+                let separator = if i == 0 { " " } else { ", " };
+                match count {
+                    1 => write!(f, "{}{}", separator, queue.steps[i])?,
+                    _ => write!(f, "{}{} x{}", separator, queue.steps[i], count)?,
+                }
+                i += count;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+// Progress of a single step of the build order.
+#[derive(PartialEq, Clone, Debug)]
+pub struct StepReport {
+    pub builder: String,
+    pub unit: String,
+    pub unit_idx: Option<usize>, // Points to the constructed unit in the world unit list
+    pub started: Option<f32>,
+    pub finished: Option<f32>,
+    // Why the step could not be started, e.g. because there was no free geo vent
+    pub failed: Option<String>,
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct BuildOrderReport {
+    pub steps: Vec<StepReport>,
+    pub end_time: f32,
+}
+
+
+impl BuildOrderReport {
+    pub fn is_complete(&self) -> bool {
+        self.steps.iter().all(|step| step.finished.is_some())
+    }
+
+
+    // Time at which the last step was finished.
+    pub fn finish_time(&self) -> Option<f32> {
+        self.steps.iter().map(|step| step.finished).try_fold(0.0, |latest: f32, finished| Some(latest.max(finished?)))
+    }
+}
+
+
+impl fmt::Display for BuildOrderReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_time = |time: Option<f32>| match time {
+            Some(time) => format!("{:7.1}s", time),
+            None => "       -".to_string(),
+        };
+        writeln!(f, "   start    finish  builder         unit")?;
+        for step in &self.steps {
+            write!(f, "{}  {}  {:<14}  {}", format_time(step.started), format_time(step.finished), step.builder, step.unit)?;
+            match &step.failed {
+                Some(reason) => writeln!(f, " (failed: {})", reason)?,
+                None => writeln!(f)?,
+            }
+        }
+        write!(f, "Simulated until {:.1}s", self.end_time)
+    }
+}
+
+
+// Executes a build order through the game state.
+#[derive(Clone, Debug)]
+pub struct BuildOrderRunner {
+    build_order: BuildOrder,
+    builders: Vec<Option<usize>>, // Unit that executes each queue
+    next_steps: Vec<usize>,
+    steps: Vec<Vec<StepReport>>,
+    // Time the builder of each queue waits before starting each step, like a player reacting to an idle builder.
+    delays: Vec<Vec<f32>>,
+    idle_since: Vec<Option<f32>>,
+}
+
+
+impl BuildOrderRunner {
+    pub fn new(build_order: BuildOrder, state: &GameState) -> Result<BuildOrderRunner, Box<dyn Error>> {
+        build_order.validate(&state.unit_catalog)?;
+        let steps = build_order.queues.iter().map(|queue| {
+            queue.steps.iter().map(|unit| StepReport {
+                builder: queue.builder.clone(),
+                unit: unit.clone(),
+                unit_idx: None,
+                started: None,
+                finished: None,
+                failed: None,
+            }).collect()
+        }).collect();
+        Ok(BuildOrderRunner {
+            builders: vec![None; build_order.queues.len()],
+            next_steps: vec![0; build_order.queues.len()],
+            delays: Vec::new(),
+            idle_since: vec![None; build_order.queues.len()],
+            build_order,
+            steps,
+        })
+    }
+
+
+    // Sets the delay before each step, by queue and step index. Missing delays are zero.
+    pub fn set_delays(&mut self, delays: Vec<Vec<f32>>) {
+        self.delays = delays;
+    }
+
+
+    pub fn is_complete(&self) -> bool {
+        self.steps.iter().flatten().all(|step| step.finished.is_some())
+    }
+
+
+    // Whether every step has either been finished or failed, so that the runner has nothing left to do.
+    pub fn is_done(&self) -> bool {
+        self.steps.iter().flatten().all(|step| step.finished.is_some() || step.failed.is_some())
+    }
+
+
+    // Hands out the next steps to idle builders and advances the simulation.
+    // Steps that cannot be started are marked as failed and the builder continues with its next step right away.
+    pub fn step(&mut self, state: &mut GameState, dt: f32) {
+        self.assign_builders(state);
+
+        for (queue_idx, builder) in self.builders.iter().enumerate() {
+            let Some(builder) = *builder else {
+                continue;
+            };
+            let unit = &state.units[builder];
+            let idle = unit.alive && unit.build_target.is_none() && unit.reclaim_target.is_none() && unit.commands.is_empty();
+            if !idle {
+                self.idle_since[queue_idx] = None;
+                continue;
+            }
+            let next_step = self.next_steps[queue_idx];
+            let idle_since = *self.idle_since[queue_idx].get_or_insert(state.time);
+            let delay = self.delays.get(queue_idx).and_then(|delays| delays.get(next_step)).copied().unwrap_or(0.0);
+            if state.time - idle_since + 1e-4 < delay {
+                continue;
+            }
+            while let Some(step) = self.steps[queue_idx].get_mut(self.next_steps[queue_idx]) {
+                self.next_steps[queue_idx] += 1;
+                match state.build_unit(builder, &step.unit) {
+                    Ok(unit_idx) => {
+                        step.unit_idx = Some(unit_idx);
+                        step.started = Some(state.time);
+                        self.idle_since[queue_idx] = None;
+                        break;
+                    }
+                    Err(e) => step.failed = Some(e.to_string()),
+                }
+            }
+        }
+
+        state.simulate(dt);
+
+        for step in self.steps.iter_mut().flatten() {
+            if let Some(unit_idx) = step.unit_idx && step.finished.is_none() && state.units[unit_idx].alive {
+                step.finished = Some(state.time);
+            }
+        }
+    }
+
+
+    // Run until every step is finished or failed, or the time limit is reached.
+    pub fn run(&mut self, state: &mut GameState, dt: f32, max_time: f32) -> Result<BuildOrderReport, Box<dyn Error>> {
+        validate_time_step(dt)?;
+        while !self.is_done() && state.time < max_time {
+            self.step(state, dt);
+        }
+        Ok(self.report(state))
+    }
+
+
+    pub fn report(&self, state: &GameState) -> BuildOrderReport {
+        BuildOrderReport {
+            steps: self.steps.iter().flatten().cloned().collect(),
+            end_time: state.time,
+        }
+    }
+
+
+    // Every queue is executed by the first completed unit of its kind that does not have a queue yet.
+    fn assign_builders(&mut self, state: &GameState) {
+        for queue_idx in 0..self.builders.len() {
+            if self.builders[queue_idx].is_some() {
+                continue;
+            }
+            let label = &self.build_order.queues[queue_idx].builder;
+            self.builders[queue_idx] = (0..state.units.len()).find(|idx| {
+                state.units[*idx].alive && state.units[*idx].def_name == *label && !self.builders.contains(&Some(*idx))
+            });
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world_params::WorldParams;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_parse() {
+        let build_order = BuildOrder::parse("commander: mex, mex, wind x3, lab; lab: con x2\n# Second constructor\nlab: con").unwrap();
+        assert_eq!(build_order.queues.len(), 3);
+        assert_eq!(build_order.queues[0].builder, "commander");
+        assert_eq!(build_order.queues[0].steps, vec!["mex", "mex", "wind", "wind", "wind", "lab"]);
+        assert_eq!(build_order.queues[1].steps, vec!["con", "con"]);
+        assert_eq!(build_order.queues[2].steps, vec!["con"]);
+        assert_eq!(build_order.to_string(), "commander: mex x2, wind x3, lab; lab: con x2; lab: con");
+
+        assert!(BuildOrder::parse("mex, mex").is_err());
+        assert!(BuildOrder::parse("commander: wind xx").is_err());
+    }
+
+
+    #[test]
+    fn test_runner() {
+        let mut state = GameState::new(WorldParams::default());
+        state.wind_strength = 20.0;
+
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 300.0;
+        com.build_options.insert("wind".to_string());
+        com.build_options.insert("lab".to_string());
+        state.register_unit("commander", com);
+
+        let mut lab = Unit::new_unconstructed(100.0, 100.0, 600.0);
+        lab.buildpower = 100.0;
+        lab.build_options.insert("wind".to_string());
+        state.register_unit("lab", lab);
+        state.register_unit("wind", Unit::new_unconstructed(10.0, 10.0, 300.0));
+        state.add_completed_unit("commander").unwrap();
+
+        let build_order = BuildOrder::parse("commander: lab, wind x2; lab: wind").unwrap();
+        assert!(BuildOrderRunner::new(BuildOrder::parse("wind: lab").unwrap(), &state).is_err());
+        let mut runner = BuildOrderRunner::new(build_order, &state).unwrap();
+        let report = runner.run(&mut state, 0.5, 100.0).unwrap();
+
+        assert!(report.is_complete());
+        assert_eq!(report.steps.len(), 4);
+        // The lab takes two seconds, after which the commander and lab work in parallel.
+        assert_abs_diff_eq!(report.steps[0].finished.unwrap(), 2.0);
+        assert_abs_diff_eq!(report.steps[1].started.unwrap(), 2.0);
+        assert_abs_diff_eq!(report.steps[1].finished.unwrap(), 3.0);
+        assert_abs_diff_eq!(report.steps[2].finished.unwrap(), 4.0);
+        assert_eq!(report.steps[3].builder, "lab");
+        assert_abs_diff_eq!(report.steps[3].started.unwrap(), 2.0);
+        assert_abs_diff_eq!(report.steps[3].finished.unwrap(), 5.0);
+        assert_abs_diff_eq!(report.finish_time().unwrap(), 5.0);
+        assert_eq!(state.units.len(), 5);
+    }
+
+
+    #[test]
+    fn test_delays() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 300.0;
+        com.build_options.insert("wind".to_string());
+        state.register_unit("commander", com);
+        state.register_unit("wind", Unit::new_unconstructed(10.0, 10.0, 300.0));
+        state.add_completed_unit("commander").unwrap();
+
+        let mut runner = BuildOrderRunner::new(BuildOrder::parse("commander: wind x3").unwrap(), &state).unwrap();
+        runner.set_delays(vec![vec![1.0, 0.0, 2.5]]);
+        let report = runner.run(&mut state, 0.5, 100.0).unwrap();
+        // Every wind takes one second. The delay counts from the moment the builder became idle.
+        assert_abs_diff_eq!(report.steps[0].started.unwrap(), 1.0);
+        assert_abs_diff_eq!(report.steps[1].started.unwrap(), 2.0);
+        assert_abs_diff_eq!(report.steps[2].started.unwrap(), 5.5);
+        assert_abs_diff_eq!(report.finish_time().unwrap(), 6.5);
+
+        assert!(runner.run(&mut state, 0.0, 100.0).is_err());
+        assert!(runner.run(&mut state, f32::NAN, 100.0).is_err());
+    }
+
+
+    #[test]
+    fn test_failed_step() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 300.0;
+        com.build_options.insert("wind".to_string());
+        com.build_options.insert("geo".to_string());
+        state.register_unit("commander", com);
+        state.register_unit("wind", Unit::new_unconstructed(10.0, 10.0, 300.0));
+        let mut geo = Unit::new_unconstructed(10.0, 10.0, 300.0);
+        geo.needs_geo = true;
+        state.register_unit("geo", geo);
+        state.add_completed_unit("commander").unwrap();
+
+        // The map has no geo vents, so the geo is skipped and the wind is built right away.
+        let mut runner = BuildOrderRunner::new(BuildOrder::parse("commander: geo, wind").unwrap(), &state).unwrap();
+        let report = runner.run(&mut state, 0.5, 100.0).unwrap();
+        assert!(runner.is_done());
+        assert!(!report.is_complete());
+        assert!(report.steps[0].failed.is_some());
+        assert_eq!(report.steps[0].started, None);
+        assert_abs_diff_eq!(report.steps[1].started.unwrap(), 0.0);
+        assert_abs_diff_eq!(report.steps[1].finished.unwrap(), 1.0);
+        assert!(report.finish_time().is_none());
+        assert_abs_diff_eq!(report.end_time, 1.0);
+        assert!(report.to_string().contains("geo (failed: "));
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::scenario::Scenario;
+use crate::unit::Unit;
+
+// Economy data of a unit, named like the keys of the unit definitions.
+fn economy_fields(unit: &Unit) -> [(&'static str, f32); 11] {
+    [
+        ("metalcost", unit.m_build_cost),
+        ("energycost", unit.e_build_cost),
+        ("buildtime", unit.buildtime),
+        ("workertime", unit.buildpower),
+        ("metalmake", unit.m_per_second),
+        ("energymake", unit.e_per_second),
+        ("energyupkeep", unit.e_cost_per_second),
+        ("windgenerator", unit.wind_e_per_second),
+        ("tidalgenerator", unit.tidal_e_multiplier),
+        ("metalstorage", unit.m_storage),
+        ("energystorage", unit.e_storage),
+    ]
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: f32,
+    pub new: f32,
+}
+
+
+impl FieldChange {
+    // Relative change, if the old value was not zero.
+    pub fn relative(&self) -> Option<f32> {
+        (self.old != 0.0).then(|| (self.new - self.old) / self.old.abs())
+    }
+}
+
+
+// Changes to a unit that exists in both catalogs.
+#[derive(PartialEq, Clone, Debug)]
+pub struct UnitChange {
+    pub unit: String,
+    pub fields: Vec<FieldChange>,
+    // Sorted build options that only the new or only the old version has.
+    pub added_options: Vec<String>,
+    pub removed_options: Vec<String>,
+}
+
+
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct CatalogDiff {
+    // Sorted names of units that only the new or only the old catalog has.
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    // Sorted by unit name.
+    pub changed: Vec<UnitChange>,
+}
+
+
+impl CatalogDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+
+impl fmt::Display for CatalogDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No economy changes")
+        }
+        if !self.added.is_empty() {
+            writeln!(f, "Added:   {}", self.added.join(", "))?;
+        }
+        if !self.removed.is_empty() {
+            writeln!(f, "Removed: {}", self.removed.join(", "))?;
+        }
+        for change in &self.changed {
+            writeln!(f, "{}:", change.unit)?;
+            for field in &change.fields {
+                let relative = match field.relative() {
+                    Some(relative) => format!(" ({:+.1}%)", 100.0 * relative),
+                    None => String::new(),
+                };
+                writeln!(f, "    {:<15} {} -> {}{}", field.field, field.old, field.new, relative)?;
+            }
+            let options: Vec<String> = change.added_options.iter().map(|option| format!("+{}", option))
+                .chain(change.removed_options.iter().map(|option| format!("-{}", option)))
+                .collect();
+            if !options.is_empty() {
+                writeln!(f, "    {:<15} {}", "buildoptions", options.join(" "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+// Compares the economy data of two versions of a catalog.
+pub fn diff_catalogs(old: &HashMap<String, Unit>, new: &HashMap<String, Unit>) -> CatalogDiff {
+    let mut diff = CatalogDiff {
+        added: new.keys().filter(|name| !old.contains_key(*name)).cloned().collect(),
+        removed: old.keys().filter(|name| !new.contains_key(*name)).cloned().collect(),
+        changed: Vec::new(),
+    };
+    diff.added.sort();
+    diff.removed.sort();
+    for (name, old_unit) in old {
+        let Some(new_unit) = new.get(name) else {
+            continue;
+        };
+        let fields: Vec<FieldChange> = economy_fields(old_unit).into_iter().zip(economy_fields(new_unit))
+            .filter(|((_, old), (_, new))| old != new)
+            .map(|((field, old), (_, new))| FieldChange { field, old, new })
+            .collect();
+        let mut added_options: Vec<String> = new_unit.build_options.difference(&old_unit.build_options).cloned().collect();
+        let mut removed_options: Vec<String> = old_unit.build_options.difference(&new_unit.build_options).cloned().collect();
+        added_options.sort();
+        removed_options.sort();
+        if !fields.is_empty() || !added_options.is_empty() || !removed_options.is_empty() {
+            diff.changed.push(UnitChange { unit: name.clone(), fields, added_options, removed_options });
+        }
+    }
+    diff.changed.sort_by(|a, b| a.unit.cmp(&b.unit));
+    diff
+}
+
+
+// When a step of the build order finishes with either version of the catalog.
+#[derive(PartialEq, Clone, Debug)]
+pub struct StepShift {
+    pub builder: String,
+    pub unit: String,
+    pub old: Option<f32>,
+    pub new: Option<f32>,
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct TimingShift {
+    pub scenario: String,
+    pub steps: Vec<StepShift>,
+}
+
+
+impl fmt::Display for TimingShift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_time = |time: Option<f32>| match time {
+            Some(time) => format!("{:7.1}s", time),
+            None => "       -".to_string(),
+        };
+        writeln!(f, "{}:", self.scenario)?;
+        write!(f, "       old       new     shift  builder         unit")?;
+        for step in &self.steps {
+            let shift = match (step.old, step.new) {
+                (Some(old), Some(new)) => format!("{:+7.1}s", new - old),
+                _ => "       -".to_string(),
+            };
+            write!(f, "\n  {}  {}  {}  {:<14}  {}", format_time(step.old), format_time(step.new), shift, step.builder, step.unit)?;
+        }
+        Ok(())
+    }
+}
+
+
+// Runs the scenario once with each version of the catalog and compares when the steps finish.
+// Units of the scenario catalog that are in neither version are kept, so that scenarios can add their own units.
+pub fn compare_timings(scenario: &Scenario, old: &HashMap<String, Unit>, new: &HashMap<String, Unit>)
+    -> Result<TimingShift, Box<dyn Error>> {
+    let run = |label: &str, version: &HashMap<String, Unit>, other: &HashMap<String, Unit>| {
+        let mut scenario = scenario.clone();
+        scenario.catalog.retain(|name, _| !other.contains_key(name));
+        scenario.catalog.extend(version.iter().map(|(name, unit)| (name.clone(), unit.clone())));
+        scenario.run().map_err(|e| format!("{} failed with the {} catalog: {}", scenario.name, label, e))
+    };
+    let (_, old_report) = run("old", old, new)?;
+    let (_, new_report) = run("new", new, old)?;
+    let steps = old_report.steps.into_iter().zip(new_report.steps).map(|(old_step, new_step)| StepShift {
+        builder: old_step.builder,
+        unit: old_step.unit,
+        old: old_step.finished,
+        new: new_step.finished,
+    }).collect();
+    Ok(TimingShift { scenario: scenario.name.clone(), steps })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_catalog() -> HashMap<String, Unit> {
+        let mut catalog = HashMap::new();
+        let mut com = Unit::new_unconstructed(1000.0, 10000.0, 50000.0);
+        com.buildpower = 300.0;
+        com.build_options.insert("mex".to_string());
+        com.build_options.insert("solar".to_string());
+        catalog.insert("commander".to_string(), com);
+        let mut mex = Unit::new_unconstructed(50.0, 500.0, 1800.0);
+        mex.m_per_second = 2.0;
+        catalog.insert("mex".to_string(), mex);
+        catalog.insert("solar".to_string(), Unit::new_unconstructed(155.0, 0.0, 2600.0));
+        catalog
+    }
+
+
+    #[test]
+    fn test_diff() {
+        let old = create_catalog();
+        assert!(diff_catalogs(&old, &old).is_empty());
+
+        let mut new = create_catalog();
+        new.remove("solar");
+        new.insert("wind".to_string(), Unit::new_unconstructed(40.0, 175.0, 1600.0));
+        let com = new.get_mut("commander").unwrap();
+        com.build_options.remove("solar");
+        com.build_options.insert("wind".to_string());
+        let mex = new.get_mut("mex").unwrap();
+        mex.m_build_cost = 60.0;
+        mex.buildtime = 1900.0;
+
+        let diff = diff_catalogs(&old, &new);
+        assert_eq!(diff.added, vec!["wind"]);
+        assert_eq!(diff.removed, vec!["solar"]);
+        assert_eq!(diff.changed.len(), 2);
+        assert_eq!(diff.changed[0].added_options, vec!["wind"]);
+        assert_eq!(diff.changed[0].removed_options, vec!["solar"]);
+        assert!(diff.changed[0].fields.is_empty());
+        assert_eq!(diff.changed[1].fields, vec![
+            FieldChange { field: "metalcost", old: 50.0, new: 60.0 },
+            FieldChange { field: "buildtime", old: 1800.0, new: 1900.0 },
+        ]);
+        assert_eq!(diff.changed[1].fields[0].relative(), Some(0.2));
+        assert!(diff.to_string().contains("    metalcost       50 -> 60 (+20.0%)\n"));
+        assert!(diff.to_string().contains("    buildoptions    +wind -solar\n"));
+    }
+}
@@ -0,0 +1,261 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::recorder::Sample;
+
+// Colors of the runs, in the order they are passed to the chart.
+const COLORS: [&str; 6] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b"];
+
+const WIDTH: f32 = 800.0;
+const HEIGHT: f32 = 400.0;
+const MARGIN_LEFT: f32 = 70.0;
+const MARGIN_RIGHT: f32 = 180.0;
+const MARGIN_TOP: f32 = 40.0;
+const MARGIN_BOTTOM: f32 = 40.0;
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Resource {
+    Metal,
+    Energy,
+}
+
+
+impl Resource {
+    fn name(&self) -> &'static str {
+        match self {
+            Resource::Metal => "Metal",
+            Resource::Energy => "Energy",
+        }
+    }
+}
+
+
+// Samples of one simulation run, shown in its own color.
+#[derive(Clone, Copy, Debug)]
+pub struct Run<'a> {
+    pub name: &'a str,
+    pub samples: &'a [Sample],
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Line {
+    pub label: String,
+    pub color: String,
+    pub dashed: bool,
+    pub points: Vec<(f32, f32)>,
+}
+
+
+// A chart of lines over time.
+#[derive(PartialEq, Clone, Debug)]
+pub struct LineChart {
+    pub title: String,
+    pub y_label: String,
+    pub lines: Vec<Line>,
+}
+
+
+impl LineChart {
+    pub fn new(title: &str, y_label: &str) -> LineChart {
+        LineChart { title: title.to_string(), y_label: y_label.to_string(), lines: Vec::new() }
+    }
+
+
+    pub fn add_line(&mut self, label: &str, color: &str, dashed: bool, points: Vec<(f32, f32)>) {
+        self.lines.push(Line { label: label.to_string(), color: color.to_string(), dashed, points });
+    }
+
+
+    pub fn to_svg(&self) -> String {
+        let points = self.lines.iter().flat_map(|line| line.points.iter());
+        let (max_x, max_y) = points.fold((0.0f32, 0.0f32), |(max_x, max_y), (x, y)| (max_x.max(*x), max_y.max(*y)));
+        let x_step = nice_step(max_x);
+        let y_step = nice_step(max_y);
+        // Round up to the next grid line, ignoring the rounding errors of accumulated time steps.
+        let max_x = (max_x / x_step - 1e-3).ceil().max(1.0) * x_step;
+        let max_y = (max_y / y_step - 1e-3).ceil().max(1.0) * y_step;
+        let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+        let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+        let to_x = |x: f32| MARGIN_LEFT + plot_width * x / max_x;
+        let to_y = |y: f32| MARGIN_TOP + plot_height * (1.0 - y / max_y);
+
+        let mut svg = String::new();
+        writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#, WIDTH, HEIGHT).unwrap();
+        writeln!(svg, r#"<text x="{}" y="20" font-size="16">{}</text>"#, MARGIN_LEFT, escape(&self.title)).unwrap();
+        writeln!(svg, r#"<text x="12" y="{}" transform="rotate(-90 12 {})" text-anchor="middle">{}</text>"#,
+            MARGIN_TOP + 0.5 * plot_height, MARGIN_TOP + 0.5 * plot_height, escape(&self.y_label)).unwrap();
+
+        // Grid and axis labels
+        let mut y = 0.0;
+        while y <= max_y + 0.5 * y_step {
+            writeln!(svg, r##"<line x1="{}" y1="{:.1}" x2="{}" y2="{:.1}" stroke="#dddddd"/>"##, MARGIN_LEFT, to_y(y), MARGIN_LEFT + plot_width, to_y(y)).unwrap();
+            writeln!(svg, r#"<text x="{}" y="{:.1}" text-anchor="end">{}</text>"#, MARGIN_LEFT - 4.0, to_y(y) + 4.0, y).unwrap();
+            y += y_step;
+        }
+        let mut x = 0.0;
+        while x <= max_x + 0.5 * x_step {
+            writeln!(svg, r#"<text x="{:.1}" y="{}" text-anchor="middle">{}s</text>"#, to_x(x), HEIGHT - MARGIN_BOTTOM + 16.0, x).unwrap();
+            x += x_step;
+        }
+        writeln!(svg, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="black"/>"#, MARGIN_LEFT, MARGIN_TOP, plot_width, plot_height).unwrap();
+
+        for (line_idx, line) in self.lines.iter().enumerate() {
+            let path: Vec<String> = line.points.iter().map(|(x, y)| format!("{:.1},{:.1}", to_x(*x), to_y(*y))).collect();
+            let dash = if line.dashed { r#" stroke-dasharray="6 4""# } else { "" };
+            writeln!(svg, r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1.5"{}/>"#, path.join(" "), line.color, dash).unwrap();
+
+            // Legend
+            let legend_x = WIDTH - MARGIN_RIGHT + 10.0;
+            let legend_y = MARGIN_TOP + 10.0 + 18.0 * line_idx as f32;
+            writeln!(svg, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="1.5"{}/>"#,
+                legend_x, legend_y, legend_x + 24.0, legend_y, line.color, dash).unwrap();
+            writeln!(svg, r#"<text x="{}" y="{}">{}</text>"#, legend_x + 30.0, legend_y + 4.0, escape(&line.label)).unwrap();
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+
+    pub fn write_svg(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_svg())?;
+        Ok(())
+    }
+}
+
+
+// Step between grid lines, so that there are about five of them: 1, 2 or 5 times a power of ten.
+fn nice_step(max: f32) -> f32 {
+    if max <= 0.0 {
+        return 1.0;
+    }
+    let rough = max / 5.0;
+    let magnitude = 10.0f32.powf(rough.log10().floor());
+    match rough / magnitude {
+        r if r <= 1.0 => magnitude,
+        r if r <= 2.0 => 2.0 * magnitude,
+        r if r <= 5.0 => 5.0 * magnitude,
+        _ => 10.0 * magnitude,
+    }
+}
+
+
+// Escapes text for use in SVG files.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+
+fn series(samples: &[Sample], value: impl Fn(&Sample) -> f32) -> Vec<(f32, f32)> {
+    samples.iter().map(|sample| (sample.time, value(sample))).collect()
+}
+
+
+// Stored resources as solid lines and the storage capacity as dashed lines.
+pub fn storage_chart(runs: &[Run], resource: Resource) -> LineChart {
+    let mut chart = LineChart::new(&format!("Stored {}", resource.name().to_lowercase()), resource.name());
+    for (run_idx, run) in runs.iter().enumerate() {
+        let color = COLORS[run_idx % COLORS.len()];
+        let (stored, capacity) = match resource {
+            Resource::Metal => (series(run.samples, |s| s.metal), series(run.samples, |s| s.metal_storage)),
+            Resource::Energy => (series(run.samples, |s| s.energy), series(run.samples, |s| s.energy_storage)),
+        };
+        chart.add_line(&format!("{} stored", run.name), color, false, stored);
+        chart.add_line(&format!("{} capacity", run.name), color, true, capacity);
+    }
+    chart
+}
+
+
+// Income as solid lines and expense as dashed lines.
+pub fn income_chart(runs: &[Run], resource: Resource) -> LineChart {
+    let mut chart = LineChart::new(&format!("{} income and expense", resource.name()), &format!("{} per second", resource.name()));
+    for (run_idx, run) in runs.iter().enumerate() {
+        let color = COLORS[run_idx % COLORS.len()];
+        let (income, expense) = match resource {
+            Resource::Metal => (series(run.samples, |s| s.metal_income), series(run.samples, |s| s.metal_expense)),
+            Resource::Energy => (series(run.samples, |s| s.energy_income), series(run.samples, |s| s.energy_expense)),
+        };
+        chart.add_line(&format!("{} income", run.name), color, false, income);
+        chart.add_line(&format!("{} expense", run.name), color, true, expense);
+    }
+    chart
+}
+
+
+// Resources lost to full storage since the start of the game.
+pub fn waste_chart(runs: &[Run], resource: Resource) -> LineChart {
+    let mut chart = LineChart::new(&format!("Wasted {}", resource.name().to_lowercase()), resource.name());
+    for (run_idx, run) in runs.iter().enumerate() {
+        let color = COLORS[run_idx % COLORS.len()];
+        let wasted = match resource {
+            Resource::Metal => series(run.samples, |s| s.metal_wasted),
+            Resource::Energy => series(run.samples, |s| s.energy_wasted),
+        };
+        chart.add_line(run.name, color, false, wasted);
+    }
+    chart
+}
+
+
+// Writes all charts for both resources into the directory, which is created if needed.
+pub fn write_charts(dir: &Path, runs: &[Run]) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    for (resource, prefix) in [(Resource::Metal, "metal"), (Resource::Energy, "energy")] {
+        storage_chart(runs, resource).write_svg(&dir.join(format!("{}_storage.svg", prefix)))?;
+        income_chart(runs, resource).write_svg(&dir.join(format!("{}_income.svg", prefix)))?;
+        waste_chart(runs, resource).write_svg(&dir.join(format!("{}_waste.svg", prefix)))?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::GameState;
+    use crate::recorder::{Observer, Recorder};
+    use crate::world_params::WorldParams;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_nice_step() {
+        assert_abs_diff_eq!(nice_step(1000.0), 200.0);
+        assert_abs_diff_eq!(nice_step(30.0), 10.0);
+        assert_abs_diff_eq!(nice_step(4.0), 1.0);
+        assert_abs_diff_eq!(nice_step(0.0), 1.0);
+    }
+
+
+    #[test]
+    fn test_overlay() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut recorder = Recorder::new(1.0).unwrap();
+        recorder.record(&state);
+        recorder.simulate(&mut state, 1.0);
+
+        let runs = [
+            Run { name: "first", samples: &recorder.samples },
+            Run { name: "second", samples: &recorder.samples[..1] },
+        ];
+        let chart = storage_chart(&runs, Resource::Metal);
+        assert_eq!(chart.lines.len(), 4);
+        assert_eq!(chart.lines[0].label, "first stored");
+        assert_eq!(chart.lines[0].points, vec![(0.0, 1000.0), (1.0, 500.0)]);
+        assert!(chart.lines[1].dashed);
+        assert_eq!(chart.lines[1].points, vec![(0.0, 500.0), (1.0, 500.0)]);
+        assert_eq!(chart.lines[2].color, COLORS[1]);
+
+        let chart = waste_chart(&runs, Resource::Energy);
+        assert_eq!(chart.lines.len(), 2);
+        assert_eq!(chart.lines[0].points[1], (1.0, 500.0));
+
+        let svg = income_chart(&runs, Resource::Energy).to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<polyline").count(), 4);
+        assert!(svg.contains("second expense"));
+    }
+}
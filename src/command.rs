@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+use crate::map::Position;
+
+// Orders that are queued on a unit and executed one after another once the unit is idle.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum Command {
+    // Walk to the position.
+    Move(Position),
+    // Walk into build range of the position and start constructing the unit there.
+    // Without a position the unit is built where the builder stands.
+    Build { unit: String, position: Option<Position> },
+    // Walk into build range of the feature and reclaim it. Points to the feature in the world feature list.
+    Reclaim(usize),
+}
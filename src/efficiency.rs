@@ -0,0 +1,191 @@
+use std::fmt;
+
+use crate::game_state::GameState;
+use crate::unit::Unit;
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct EfficiencyConfig {
+    // Energy that is worth as much as one metal. Defaults to the rate of the tech 1 energy converter.
+    pub energy_per_metal: f32,
+    // Build power that constructs the producer.
+    pub build_power: f32,
+}
+
+
+impl Default for EfficiencyConfig {
+    fn default() -> Self {
+        EfficiencyConfig {
+            energy_per_metal: 70.0,
+            build_power: 300.0,
+        }
+    }
+}
+
+
+// How well a producer pays for itself under the conditions of a game state.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Efficiency {
+    pub unit: String,
+    // Metal plus energy converted to metal
+    pub cost: f32,
+    pub metal_income: f32,
+    // Production minus upkeep
+    pub energy_income: f32,
+    // Net income converted to metal
+    pub income: f32,
+    // Time to construct the unit with the configured build power
+    pub build_time: f32,
+    // Time from starting construction until the income has paid back the cost.
+    // None if the unit does not make a net profit.
+    pub payback_time: Option<f32>,
+    pub needs_geo: bool,
+}
+
+
+impl Efficiency {
+    pub fn new(name: &str, unit: &Unit, state: &GameState, wind_strength: f32, config: &EfficiencyConfig) -> Efficiency {
+        let cost = unit.m_build_cost + unit.e_build_cost / config.energy_per_metal;
+        let energy_income = unit.e_per_second + unit.wind_e_per_second.min(wind_strength)
+            + unit.tidal_e_multiplier * state.tidal_strength - unit.e_cost_per_second;
+        let income = unit.m_per_second + energy_income / config.energy_per_metal;
+        let build_time = unit.buildtime / config.build_power;
+        Efficiency {
+            unit: name.to_string(),
+            cost,
+            metal_income: unit.m_per_second,
+            energy_income,
+            income,
+            build_time,
+            payback_time: (income > 0.0).then(|| build_time + cost / income),
+            needs_geo: unit.needs_geo,
+        }
+    }
+}
+
+
+pub fn is_producer(unit: &Unit) -> bool {
+    unit.m_per_second > 0.0 || unit.e_per_second > 0.0 || unit.wind_e_per_second > 0.0 || unit.tidal_e_multiplier > 0.0
+}
+
+
+// Every producer of the catalog at the wind strength of the state, the fastest payback first.
+// Units that never pay back come last.
+pub fn analyze_producers(state: &GameState, config: &EfficiencyConfig) -> Vec<Efficiency> {
+    let mut producers: Vec<Efficiency> = state.unit_catalog.iter()
+        .filter(|(_, unit)| is_producer(unit))
+        .map(|(name, unit)| Efficiency::new(name, unit, state, state.wind_strength, config))
+        .collect();
+    producers.sort_by(|a, b| {
+        let a_time = a.payback_time.unwrap_or(f32::INFINITY);
+        let b_time = b.payback_time.unwrap_or(f32::INFINITY);
+        a_time.total_cmp(&b_time).then_with(|| a.unit.cmp(&b.unit))
+    });
+    producers
+}
+
+
+// Efficiency of the unit at each of the wind strengths.
+pub fn wind_curve(state: &GameState, name: &str, config: &EfficiencyConfig, winds: &[f32]) -> Option<Vec<Efficiency>> {
+    let unit = state.unit_catalog.get(name)?;
+    Some(winds.iter().map(|wind| Efficiency::new(name, unit, state, *wind, config)).collect())
+}
+
+
+// Lowest wind strength, in steps of 0.1, from which the unit pays back at least as fast as the other unit.
+// None if that never happens, e.g. because the unit does not use wind.
+pub fn break_even_wind(state: &GameState, name: &str, other: &str, config: &EfficiencyConfig) -> Option<f32> {
+    let unit = state.unit_catalog.get(name)?;
+    let other = Efficiency::new(other, state.unit_catalog.get(other)?, state, state.wind_strength, config);
+    let other_time = other.payback_time?;
+    let steps = (10.0 * unit.wind_e_per_second).ceil() as usize;
+    (0..=steps).map(|step| step as f32 / 10.0).find(|wind| {
+        Efficiency::new(name, unit, state, *wind, config).payback_time.is_some_and(|time| time <= other_time)
+    })
+}
+
+
+// Table of producers, as returned by `analyze_producers`.
+pub struct EfficiencyTable<'a>(pub &'a [Efficiency]);
+
+
+impl fmt::Display for EfficiencyTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unit                cost    metal/s   energy/s   income/s  build time    payback")?;
+        for row in self.0 {
+            let payback = match row.payback_time {
+                Some(time) => format!("{:9.1}s", time),
+                None => "     never".to_string(),
+            };
+            let name = if row.needs_geo { format!("{} (geo)", row.unit) } else { row.unit.clone() };
+            write!(f, "\n{:<16} {:7.1}  {:9.2}  {:9.2}  {:9.3}  {:9.1}s  {}",
+                name, row.cost, row.metal_income, row.energy_income, row.income, row.build_time, payback)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world_params::WorldParams;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_payback() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut mex = Unit::new_unconstructed(50.0, 500.0, 1800.0);
+        mex.m_per_second = 2.0;
+        mex.e_cost_per_second = 3.0;
+        state.register_unit("mex", mex);
+        let mut solar = Unit::new_unconstructed(155.0, 0.0, 2600.0);
+        solar.e_per_second = 20.0;
+        state.register_unit("solar", solar);
+        let mut wind = Unit::new_unconstructed(40.0, 175.0, 1600.0);
+        wind.wind_e_per_second = 25.0;
+        state.register_unit("wind", wind);
+        state.register_unit("lab", Unit::new_unconstructed(650.0, 1300.0, 7000.0));
+        state.wind_strength = 7.0;
+
+        let config = EfficiencyConfig { energy_per_metal: 70.0, build_power: 100.0 };
+        let producers = analyze_producers(&state, &config);
+        let names: Vec<&str> = producers.iter().map(|row| row.unit.as_str()).collect();
+        assert_eq!(names, vec!["mex", "wind", "solar"]);
+
+        let mex = &producers[0];
+        assert_abs_diff_eq!(mex.cost, 50.0 + 500.0 / 70.0);
+        assert_abs_diff_eq!(mex.energy_income, -3.0);
+        assert_abs_diff_eq!(mex.income, 2.0 - 3.0 / 70.0);
+        assert_abs_diff_eq!(mex.payback_time.unwrap(), 18.0 + mex.cost / mex.income, epsilon = 1e-3);
+        // Wind is capped by the wind strength.
+        assert_abs_diff_eq!(producers[1].energy_income, 7.0);
+        assert_abs_diff_eq!(producers[2].payback_time.unwrap(), 26.0 + 155.0 * 70.0 / 20.0, epsilon = 1e-2);
+    }
+
+
+    #[test]
+    fn test_wind() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut mex = Unit::new_unconstructed(50.0, 500.0, 1800.0);
+        mex.m_per_second = 2.0;
+        state.register_unit("mex", mex);
+        let mut solar = Unit::new_unconstructed(155.0, 0.0, 2600.0);
+        solar.e_per_second = 20.0;
+        state.register_unit("solar", solar);
+        let mut wind = Unit::new_unconstructed(40.0, 175.0, 1600.0);
+        wind.wind_e_per_second = 25.0;
+        state.register_unit("wind", wind);
+
+        let config = EfficiencyConfig::default();
+        let curve = wind_curve(&state, "wind", &config, &[0.0, 10.0, 30.0]).unwrap();
+        assert!(curve[0].payback_time.is_none());
+        assert_abs_diff_eq!(curve[1].energy_income, 10.0);
+        assert_abs_diff_eq!(curve[2].energy_income, 25.0);
+
+        // The wind costs 42.5 metal and the solar 155, so the wind needs about a quarter of the solar's 20 energy,
+        // minus a bit for its shorter build time.
+        let wind = break_even_wind(&state, "wind", "solar", &config).unwrap();
+        assert_abs_diff_eq!(wind, 5.5, epsilon = 0.1);
+        assert!(break_even_wind(&state, "solar", "mex", &config).is_none());
+    }
+}
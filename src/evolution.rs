@@ -0,0 +1,437 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+
+use crate::build_order::{validate_time_step, BuildOrder, BuildOrderReport, BuildOrderRunner, BuildQueue};
+use crate::game_state::GameState;
+use crate::optimizer::{build_options, builders, Objective};
+use crate::recorder::{Observer, Recorder, Sample};
+
+// Everything that happened while a build order was simulated, handed to the fitness function.
+pub struct Simulation {
+    pub state: GameState,
+    pub report: BuildOrderReport,
+    pub samples: Vec<Sample>,
+}
+
+
+// Rates a simulated build order. Higher is better.
+pub trait Fitness: Sync {
+    fn fitness(&self, simulation: &Simulation) -> f32;
+}
+
+
+impl<F: Fn(&Simulation) -> f32 + Sync> Fitness for F {
+    fn fitness(&self, simulation: &Simulation) -> f32 {
+        self(simulation)
+    }
+}
+
+
+impl Fitness for Objective {
+    fn fitness(&self, simulation: &Simulation) -> f32 {
+        match self {
+            Objective::MetalIncome(time) | Objective::EnergyIncome(time) => {
+                let sample = simulation.samples.iter().rev().find(|sample| sample.time <= time + 1e-4);
+                match (self, sample) {
+                    (Objective::MetalIncome(_), Some(sample)) => sample.metal_income,
+                    (_, Some(sample)) => sample.energy_income,
+                    (_, None) => 0.0,
+                }
+            }
+            Objective::Reach(targets) => {
+                let mut reach_time: f32 = 0.0;
+                let mut missing = 0;
+                for (name, count) in targets {
+                    // Units that are not the result of a step existed from the start.
+                    let mut times: Vec<f32> = (0..simulation.state.units.len())
+                        .filter(|idx| simulation.state.units[*idx].alive && simulation.state.units[*idx].def_name == *name)
+                        .map(|idx| {
+                            let step = simulation.report.steps.iter().find(|step| step.unit_idx == Some(idx));
+                            step.and_then(|step| step.finished).unwrap_or(0.0)
+                        })
+                        .collect();
+                    times.sort_by(f32::total_cmp);
+                    match count.checked_sub(1).map(|idx| times.get(idx)) {
+                        Some(Some(time)) => reach_time = reach_time.max(*time),
+                        Some(None) => missing += count - times.len(),
+                        None => {}
+                    }
+                }
+                // The simulation ends at the time limit.
+                Objective::reach_score(missing, reach_time, simulation.state.time)
+            }
+        }
+    }
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct EvolutionConfig {
+    // Units that may be added to the build orders. Without a list, every unit in the catalog may be built.
+    pub allowed_units: Option<HashSet<String>>,
+    pub population: usize,
+    pub generations: usize,
+    // Maximum number of steps of a build order.
+    pub max_steps: usize,
+    // Number of best build orders that are copied unchanged into the next generation.
+    pub elite: usize,
+    // Number of build orders that compete for becoming a parent.
+    pub tournament_size: usize,
+    pub crossover_rate: f64,
+    pub mutation_rate: f64,
+    // Length of each simulation
+    pub duration: f32,
+    pub time_step: f32,
+    pub sample_interval: f32,
+    pub seed: u64,
+    // Number of threads that evaluate build orders. Zero uses all available cores.
+    pub threads: usize,
+}
+
+
+impl Default for EvolutionConfig {
+    fn default() -> Self {
+        EvolutionConfig {
+            allowed_units: None,
+            population: 32,
+            generations: 30,
+            max_steps: 15,
+            elite: 2,
+            tournament_size: 3,
+            crossover_rate: 0.7,
+            mutation_rate: 0.8,
+            duration: 300.0,
+            time_step: 0.5,
+            sample_interval: 5.0,
+            seed: 0,
+            threads: 0,
+        }
+    }
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Individual {
+    pub build_order: BuildOrder,
+    pub fitness: f32,
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct EvolutionResult {
+    pub best: Individual,
+    // Fitness of the best build order of the initial population and after every generation.
+    pub history: Vec<f32>,
+}
+
+
+fn validate_simulation(config: &EvolutionConfig) -> Result<(), Box<dyn Error>> {
+    validate_time_step(config.time_step)?;
+    if !config.sample_interval.is_finite() || config.sample_interval <= 0.0 {
+        return Err(format!("Invalid sample interval {}.", config.sample_interval).into())
+    }
+    if !config.duration.is_finite() {
+        return Err(format!("Invalid duration {}.", config.duration).into())
+    }
+    Ok(())
+}
+
+
+// Runs the build order on a copy of the state for the configured duration.
+pub fn simulate(state: &GameState, build_order: &BuildOrder, config: &EvolutionConfig) -> Result<Simulation, Box<dyn Error>> {
+    validate_simulation(config)?;
+    let mut state = state.clone();
+    let mut runner = BuildOrderRunner::new(build_order.clone(), &state)?;
+    let mut recorder = Recorder::new(config.sample_interval)?;
+    recorder.record(&state);
+    let end_time = state.time + config.duration;
+    while state.time + 1e-4 < end_time {
+        runner.step(&mut state, config.time_step);
+        recorder.record(&state);
+    }
+    let report = runner.report(&state);
+    Ok(Simulation { state, report, samples: recorder.samples })
+}
+
+
+// Evolves build orders, starting from the given ones and filling the rest of the population with random orders.
+pub fn evolve<F: Fitness + ?Sized>(state: &GameState, fitness: &F, initial: &[BuildOrder], config: &EvolutionConfig)
+    -> Result<EvolutionResult, Box<dyn Error>> {
+    if config.population == 0 {
+        return Err("The population must not be empty.".into())
+    }
+    for (name, rate) in [("crossover", config.crossover_rate), ("mutation", config.mutation_rate)] {
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(format!("The {} rate must be between 0 and 1.", name).into())
+        }
+    }
+    // Checked before the worker threads start, since each of them would hang on a simulation that does not advance.
+    validate_simulation(config)?;
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut build_orders: Vec<BuildOrder> = initial.iter().take(config.population).cloned().collect();
+    while build_orders.len() < config.population {
+        let mut build_order = BuildOrder::default();
+        for _ in 0..rng.random_range(1..=config.max_steps.max(1)) {
+            insert_step(&mut build_order, state, config, &mut rng);
+        }
+        build_orders.push(build_order);
+    }
+    let fitnesses = evaluate_all(state, &build_orders, fitness, config);
+    let mut population: Vec<Individual> = build_orders.into_iter().zip(fitnesses)
+        .map(|(build_order, fitness)| Individual { build_order, fitness })
+        .collect();
+    population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+    let mut history = vec![population[0].fitness];
+
+    for _ in 0..config.generations {
+        let mut children = Vec::new();
+        while children.len() + config.elite.min(population.len()) < config.population {
+            let parent = tournament(&population, config, &mut rng);
+            let mut child = match rng.random_bool(config.crossover_rate) {
+                true => crossover(parent, tournament(&population, config, &mut rng), config, &mut rng),
+                false => parent.clone(),
+            };
+            if rng.random_bool(config.mutation_rate) {
+                mutate(&mut child, state, config, &mut rng);
+            }
+            children.push(child);
+        }
+        let fitnesses = evaluate_all(state, &children, fitness, config);
+        population.truncate(config.elite);
+        population.extend(children.into_iter().zip(fitnesses).map(|(build_order, fitness)| Individual { build_order, fitness }));
+        population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        history.push(population[0].fitness);
+    }
+    Ok(EvolutionResult { best: population.swap_remove(0), history })
+}
+
+
+// Build orders that cannot be executed get the lowest possible fitness.
+fn evaluate<F: Fitness + ?Sized>(state: &GameState, build_order: &BuildOrder, fitness: &F, config: &EvolutionConfig) -> f32 {
+    match simulate(state, build_order, config) {
+        Ok(simulation) => fitness.fitness(&simulation),
+        Err(_) => f32::NEG_INFINITY,
+    }
+}
+
+
+// Evaluates the build orders in parallel. The result does not depend on the number of threads.
+fn evaluate_all<F: Fitness + ?Sized>(state: &GameState, build_orders: &[BuildOrder], fitness: &F, config: &EvolutionConfig) -> Vec<f32> {
+    let threads = match config.threads {
+        0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    };
+    let chunk_size = build_orders.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = build_orders.chunks(chunk_size).map(|chunk| {
+            scope.spawn(move || chunk.iter().map(|build_order| evaluate(state, build_order, fitness, config)).collect::<Vec<f32>>())
+        }).collect();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    })
+}
+
+
+fn tournament<'a>(population: &'a [Individual], config: &EvolutionConfig, rng: &mut StdRng) -> &'a BuildOrder {
+    let mut best = rng.random_range(0..population.len());
+    for _ in 1..config.tournament_size {
+        // The population is sorted, so the lower index is the fitter one.
+        best = best.min(rng.random_range(0..population.len()));
+    }
+    &population[best].build_order
+}
+
+
+fn step_count(build_order: &BuildOrder) -> usize {
+    build_order.queues.iter().map(|queue| queue.steps.len()).sum()
+}
+
+
+// Returns the queue of the builder, adding an empty one if needed.
+fn queue_mut<'a>(build_order: &'a mut BuildOrder, builder: &str) -> &'a mut BuildQueue {
+    match build_order.queues.iter().position(|queue| queue.builder == builder) {
+        Some(queue_idx) => &mut build_order.queues[queue_idx],
+        None => {
+            build_order.queues.push(BuildQueue { builder: builder.to_string(), steps: Vec::new() });
+            build_order.queues.last_mut().unwrap()
+        }
+    }
+}
+
+
+// Picks a random step as (queue index, step index).
+fn random_step(build_order: &BuildOrder, rng: &mut StdRng) -> Option<(usize, usize)> {
+    let count = step_count(build_order);
+    if count == 0 {
+        return None;
+    }
+    let mut step = rng.random_range(0..count);
+    for (queue_idx, queue) in build_order.queues.iter().enumerate() {
+        if step < queue.steps.len() {
+            return Some((queue_idx, step));
+        }
+        step -= queue.steps.len();
+    }
+    None
+}
+
+
+// Adds a random unit at a random place in the queue of a random builder.
+fn insert_step(build_order: &mut BuildOrder, state: &GameState, config: &EvolutionConfig, rng: &mut StdRng) {
+    if step_count(build_order) >= config.max_steps {
+        return;
+    }
+    let builders = builders(state, build_order);
+    if builders.is_empty() {
+        return;
+    }
+    let builder = &builders[rng.random_range(0..builders.len())];
+    let options = build_options(state, builder, &config.allowed_units);
+    if options.is_empty() {
+        return;
+    }
+    let unit = options[rng.random_range(0..options.len())].clone();
+    let queue = queue_mut(build_order, builder);
+    let position = rng.random_range(0..=queue.steps.len());
+    queue.steps.insert(position, unit);
+}
+
+
+// Applies one random change: insert, delete or swap steps, or move a step to another builder.
+fn mutate(build_order: &mut BuildOrder, state: &GameState, config: &EvolutionConfig, rng: &mut StdRng) {
+    match rng.random_range(0..4) {
+        0 => insert_step(build_order, state, config, rng),
+        1 => {
+            if let Some((queue_idx, step_idx)) = random_step(build_order, rng) {
+                build_order.queues[queue_idx].steps.remove(step_idx);
+            }
+        }
+        2 => {
+            if let Some((queue_idx, step_idx)) = random_step(build_order, rng) {
+                let steps = &mut build_order.queues[queue_idx].steps;
+                let other_idx = rng.random_range(0..steps.len());
+                steps.swap(step_idx, other_idx);
+            }
+        }
+        _ => {
+            if let Some((queue_idx, step_idx)) = random_step(build_order, rng) {
+                let unit = build_order.queues[queue_idx].steps[step_idx].clone();
+                let builder = build_order.queues[queue_idx].builder.clone();
+                let others: Vec<String> = builders(state, build_order).into_iter()
+                    .filter(|other| *other != builder && build_options(state, other, &config.allowed_units).contains(&unit))
+                    .collect();
+                if !others.is_empty() {
+                    build_order.queues[queue_idx].steps.remove(step_idx);
+                    let queue = queue_mut(build_order, &others[rng.random_range(0..others.len())]);
+                    let position = rng.random_range(0..=queue.steps.len());
+                    queue.steps.insert(position, unit);
+                }
+            }
+        }
+    }
+    build_order.queues.retain(|queue| !queue.steps.is_empty());
+}
+
+
+// Combines the start of each queue of one parent with the end of the same builder's queue of the other parent.
+fn crossover(a: &BuildOrder, b: &BuildOrder, config: &EvolutionConfig, rng: &mut StdRng) -> BuildOrder {
+    let mut child = BuildOrder::default();
+    let mut builders: Vec<&str> = Vec::new();
+    for queue in a.queues.iter().chain(&b.queues) {
+        if !builders.contains(&queue.builder.as_str()) {
+            builders.push(&queue.builder);
+        }
+    }
+    let no_steps = Vec::new();
+    let mut remaining = config.max_steps;
+    for builder in builders {
+        let steps_a = a.queues.iter().find(|queue| queue.builder == builder).map_or(&no_steps, |queue| &queue.steps);
+        let steps_b = b.queues.iter().find(|queue| queue.builder == builder).map_or(&no_steps, |queue| &queue.steps);
+        let cut_a = rng.random_range(0..=steps_a.len());
+        let cut_b = rng.random_range(0..=steps_b.len());
+        let steps: Vec<String> = steps_a[..cut_a].iter().chain(&steps_b[cut_b..]).take(remaining).cloned().collect();
+        remaining -= steps.len();
+        if !steps.is_empty() {
+            child.queues.push(BuildQueue { builder: builder.to_string(), steps });
+        }
+    }
+    child
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::fixtures::tech_1_state;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_mutations() {
+        let state = tech_1_state();
+        let config = EvolutionConfig { max_steps: 6, ..EvolutionConfig::default() };
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut a = BuildOrder::parse("commander: lab, mex; lab: con").unwrap();
+        let b = BuildOrder::parse("commander: solar x3; lab: con, con").unwrap();
+        for _ in 0..200 {
+            mutate(&mut a, &state, &config, &mut rng);
+            let child = crossover(&a, &b, &config, &mut rng);
+            for build_order in [&a, &child] {
+                assert!(build_order.validate(&state.unit_catalog).is_ok());
+                assert!(step_count(build_order) <= config.max_steps);
+                assert!(build_order.queues.iter().all(|queue| !queue.steps.is_empty()));
+            }
+        }
+    }
+
+
+    #[test]
+    fn test_evolve() {
+        let state = tech_1_state();
+        let config = EvolutionConfig {
+            allowed_units: Some(HashSet::from(["mex".to_string(), "solar".to_string()])),
+            population: 16,
+            generations: 20,
+            max_steps: 3,
+            duration: 30.0,
+            seed: 7,
+            threads: 3,
+            ..EvolutionConfig::default()
+        };
+        // Fitness over the recorded samples: the final metal income
+        let fitness = |simulation: &Simulation| simulation.samples.last().unwrap().metal_income;
+        let result = evolve(&state, &fitness, &[], &config).unwrap();
+        assert_eq!(result.best.build_order.to_string(), "commander: mex x3");
+        assert_abs_diff_eq!(result.best.fitness, 6.0);
+        assert_eq!(result.history.len(), 21);
+        assert!(result.history.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        // The same seed gives the same result, regardless of the number of threads.
+        let single_threaded = evolve(&state, &fitness, &[], &EvolutionConfig { threads: 1, ..config.clone() }).unwrap();
+        assert_eq!(single_threaded, result);
+
+        assert!(evolve(&state, &fitness, &[], &EvolutionConfig { crossover_rate: 1.5, ..config.clone() }).is_err());
+        assert!(evolve(&state, &fitness, &[], &EvolutionConfig { mutation_rate: f64::NAN, ..config.clone() }).is_err());
+        assert!(evolve(&state, &fitness, &[], &EvolutionConfig { time_step: 0.0, ..config.clone() }).is_err());
+        assert!(evolve(&state, &fitness, &[], &EvolutionConfig { sample_interval: -1.0, ..config.clone() }).is_err());
+        assert!(evolve(&state, &fitness, &[], &EvolutionConfig { duration: f32::INFINITY, ..config }).is_err());
+    }
+
+
+    #[test]
+    fn test_reach_fitness() {
+        let state = tech_1_state();
+        let config = EvolutionConfig { duration: 30.0, ..EvolutionConfig::default() };
+        let objective = Objective::parse_reach("commander, lab, con x2").unwrap();
+        let simulation = simulate(&state, &BuildOrder::parse("commander: lab; lab: con x2").unwrap(), &config).unwrap();
+        assert_abs_diff_eq!(objective.fitness(&simulation), -11.0, epsilon = 0.5);
+        let simulation = simulate(&state, &BuildOrder::parse("commander: lab; lab: con").unwrap(), &config).unwrap();
+        assert_abs_diff_eq!(objective.fitness(&simulation), -60.0, epsilon = 1e-3);
+        // No constructors at all are reached from the start.
+        let objective = Objective::Reach(HashMap::from([("con".to_string(), 0)]));
+        assert_abs_diff_eq!(objective.fitness(&simulation), 0.0);
+    }
+}
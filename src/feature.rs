@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::map::Position;
+
+// Share of the metal cost that is left in the wreck of a unit without a corpse definition.
+pub const DEFAULT_WRECK_METAL: f32 = 0.6;
+
+// Map objects like trees, rocks and wrecks that builders can reclaim for resources.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Feature {
+    pub name: String,
+    pub metal: f32,
+    pub energy: f32,
+    pub reclaim_time: f32,
+    pub position: Option<Position>,
+    // Fraction of the feature that has been reclaimed already.
+    pub reclaimed: f32,
+}
+
+
+impl Feature {
+    pub fn new(metal: f32, energy: f32, reclaim_time: f32) -> Feature {
+        Feature {
+            name: "Unnamed".to_string(),
+            metal,
+            energy,
+            reclaim_time,
+            position: None,
+            reclaimed: 0.0,
+        }
+    }
+
+
+    // The engine falls back to a reclaim time based on the feature's value.
+    pub fn default_reclaim_time(metal: f32, energy: f32) -> f32 {
+        ((metal + energy) * 6.0).max(1.0)
+    }
+
+
+    // Wreck of a unit that does not define its own corpse.
+    pub fn default_wreck(m_build_cost: f32) -> Feature {
+        let metal = DEFAULT_WRECK_METAL * m_build_cost;
+        let mut feature = Feature::new(metal, 0.0, Feature::default_reclaim_time(metal, 0.0));
+        feature.name = "wreck".to_string();
+        feature
+    }
+
+
+    pub fn is_reclaimed(&self) -> bool {
+        self.reclaimed >= 1.0
+    }
+}
@@ -0,0 +1,32 @@
+use crate::game_state::GameState;
+use crate::unit::Unit;
+use crate::world_params::WorldParams;
+
+// A commander that can build mexes, solars and a lab, which builds constructors that build mexes.
+// The units are cheap, so that searches over build orders run quickly.
+pub(crate) fn tech_1_state() -> GameState {
+    let mut state = GameState::new(WorldParams::default());
+    let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+    com.buildpower = 100.0;
+    com.e_per_second = 20.0;
+    for option in ["mex", "solar", "lab"] {
+        com.build_options.insert(option.to_string());
+    }
+    state.register_unit("commander", com);
+    let mut mex = Unit::new_unconstructed(50.0, 50.0, 100.0);
+    mex.m_per_second = 2.0;
+    state.register_unit("mex", mex);
+    let mut solar = Unit::new_unconstructed(50.0, 0.0, 100.0);
+    solar.e_per_second = 20.0;
+    state.register_unit("solar", solar);
+    let mut lab = Unit::new_unconstructed(200.0, 200.0, 500.0);
+    lab.buildpower = 100.0;
+    lab.build_options.insert("con".to_string());
+    state.register_unit("lab", lab);
+    let mut con = Unit::new_unconstructed(100.0, 100.0, 300.0);
+    con.buildpower = 50.0;
+    con.build_options.insert("mex".to_string());
+    state.register_unit("con", con);
+    state.add_completed_unit("commander").unwrap();
+    state
+}
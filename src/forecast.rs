@@ -0,0 +1,206 @@
+use std::error::Error;
+
+use crate::build_order::{validate_time_step, BuildOrderReport, BuildOrderRunner};
+use crate::charts::Resource;
+use crate::game_state::GameState;
+use crate::recorder::{Observer, Sample};
+use crate::unit::Unit;
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct ForecastConfig {
+    // How far to look ahead, in seconds
+    pub horizon: f32,
+    pub time_step: f32,
+}
+
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        ForecastConfig {
+            horizon: 60.0,
+            time_step: 0.5,
+        }
+    }
+}
+
+
+// A time span in which a resource ran short without interruption.
+#[derive(PartialEq, Clone, Debug)]
+pub struct StallWindow {
+    pub resource: Resource,
+    pub start: f32,
+    pub end: f32,
+}
+
+
+// Predicted course of the economy, assuming that the units keep following their current orders.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Forecast {
+    // The current state followed by one sample after every simulation step.
+    pub samples: Vec<Sample>,
+    // Sorted by start time.
+    pub stalls: Vec<StallWindow>,
+    // Progress of the build order, if the forecast continued one.
+    pub report: Option<BuildOrderReport>,
+}
+
+
+impl Forecast {
+    // First time at which the stored resources cover the full cost of the unit.
+    pub fn afford_time(&self, unit: &Unit) -> Option<f32> {
+        self.samples.iter()
+            .find(|sample| sample.metal >= unit.m_build_cost && sample.energy >= unit.e_build_cost)
+            .map(|sample| sample.time)
+    }
+
+
+    // First time at which resources were lost because the storage was full.
+    pub fn overflow_time(&self, resource: Resource) -> Option<f32> {
+        self.samples.iter().find(|sample| match resource {
+            Resource::Metal => sample.metal_waste > 0.0,
+            Resource::Energy => sample.energy_waste > 0.0,
+        }).map(|sample| sample.time)
+    }
+
+
+    // Total time the resource is predicted to run short.
+    pub fn stall_time(&self, resource: Resource) -> f32 {
+        let mut time = 0.0;
+        for window in self.stalls.iter().filter(|window| window.resource == resource) {
+            time += window.end - window.start;
+        }
+        time
+    }
+
+
+    pub fn last(&self) -> &Sample {
+        self.samples.last().unwrap()
+    }
+}
+
+
+// Simulates a copy of the state, so that the state itself is not changed.
+// Units continue their build targets and commands, and the runner, if given, continues handing out build order steps.
+pub fn forecast(state: &GameState, runner: Option<&BuildOrderRunner>, config: &ForecastConfig) -> Result<Forecast, Box<dyn Error>> {
+    forecast_recorded(state, runner, config, &mut [])
+}
+
+
+// Forecast that also shows every simulated step of the copied state to the observers.
+pub fn forecast_recorded(state: &GameState, runner: Option<&BuildOrderRunner>, config: &ForecastConfig,
+    observers: &mut [&mut dyn Observer]) -> Result<Forecast, Box<dyn Error>> {
+    validate_time_step(config.time_step)?;
+    if !config.horizon.is_finite() {
+        return Err(format!("Invalid horizon {}.", config.horizon).into())
+    }
+    let mut state = state.clone();
+    let mut runner = runner.cloned();
+    let end_time = state.time + config.horizon;
+    for observer in observers.iter_mut() {
+        observer.record(&state);
+    }
+    let mut samples = vec![Sample::from_state(&state)];
+    let mut stalls: Vec<StallWindow> = Vec::new();
+    while state.time + 1e-4 < end_time {
+        let start = state.time;
+        match &mut runner {
+            Some(runner) => runner.step(&mut state, config.time_step),
+            None => state.simulate(config.time_step),
+        }
+        let last_stalls = state.last_stalls();
+        for (resource, stalled) in [(Resource::Metal, last_stalls.metal_stall_time), (Resource::Energy, last_stalls.energy_stall_time)] {
+            if stalled <= 0.0 {
+                continue;
+            }
+            // Extend the window of the previous step, if there was one.
+            match stalls.iter_mut().rev().find(|window| window.resource == resource && (window.end - start).abs() < 1e-4) {
+                Some(window) => window.end = state.time,
+                None => stalls.push(StallWindow { resource, start, end: state.time }),
+            }
+        }
+        samples.push(Sample::from_state(&state));
+        for observer in observers.iter_mut() {
+            observer.record(&state);
+        }
+    }
+    Ok(Forecast { samples, stalls, report: runner.map(|runner| runner.report(&state)) })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_order::BuildOrder;
+    use crate::world_params::WorldParams;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_idle_forecast() {
+        let mut state = GameState::new(WorldParams::default());
+        state.metal = 100.0;
+        state.energy = 400.0;
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.m_per_second = 2.0;
+        com.e_per_second = 20.0;
+        state.register_unit("commander", com);
+        state.register_unit("mex", Unit::new_unconstructed(150.0, 0.0, 100.0));
+        state.register_unit("lab", Unit::new_unconstructed(300.0, 0.0, 1000.0));
+        state.add_completed_unit("commander").unwrap();
+
+        let original = state.clone();
+        let config = ForecastConfig { horizon: 30.0, time_step: 0.5 };
+        let forecast = forecast(&state, None, &config).unwrap();
+        assert_eq!(state, original);
+        assert_eq!(forecast.samples.len(), 61);
+        assert_abs_diff_eq!(forecast.last().time, 30.0, epsilon = 1e-3);
+        assert_abs_diff_eq!(forecast.last().metal, 160.0, epsilon = 1e-2);
+        assert!(forecast.stalls.is_empty());
+
+        // 2 metal per second make up for the 50 missing metal after 25 seconds.
+        let mex = &state.unit_catalog["mex"];
+        assert_abs_diff_eq!(forecast.afford_time(mex).unwrap(), 25.0, epsilon = 1e-2);
+        assert!(forecast.afford_time(&state.unit_catalog["lab"]).is_none());
+        // The 500 energy storage is full after 5 seconds.
+        assert_abs_diff_eq!(forecast.overflow_time(Resource::Energy).unwrap(), 5.0, epsilon = 0.5);
+        assert!(forecast.overflow_time(Resource::Metal).is_none());
+
+        for config in [
+            ForecastConfig { horizon: 30.0, time_step: 0.0 },
+            ForecastConfig { horizon: 30.0, time_step: f32::NAN },
+            ForecastConfig { horizon: f32::INFINITY, time_step: 0.5 },
+        ] {
+            assert!(super::forecast(&state, None, &config).is_err());
+        }
+    }
+
+
+    #[test]
+    fn test_build_order_forecast() {
+        let mut state = GameState::new(WorldParams::default());
+        state.metal = 100.0;
+        state.energy = 400.0;
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 100.0;
+        com.m_per_second = 2.0;
+        com.e_per_second = 20.0;
+        com.build_options.insert("lab".to_string());
+        state.register_unit("commander", com);
+        state.register_unit("lab", Unit::new_unconstructed(300.0, 0.0, 1000.0));
+        state.add_completed_unit("commander").unwrap();
+
+        let runner = BuildOrderRunner::new(BuildOrder::parse("commander: lab").unwrap(), &state).unwrap();
+        let config = ForecastConfig { horizon: 20.0, time_step: 0.5 };
+        let forecast = forecast(&state, Some(&runner), &config).unwrap();
+        assert!(!runner.is_complete());
+        assert_eq!(state.units.len(), 1);
+
+        // The lab takes 15 metal per step, so the 100 metal run out after less than four seconds.
+        // From then on, the builder waits for 15 metal to build a step, which takes seven and a half seconds.
+        assert!(forecast.stalls.iter().all(|window| window.resource == Resource::Metal));
+        assert_abs_diff_eq!(forecast.stalls[0].start, 3.5, epsilon = 0.5);
+        assert_abs_diff_eq!(forecast.stalls[1].start - forecast.stalls[0].end, 0.5, epsilon = 1e-3);
+        assert_abs_diff_eq!(forecast.stall_time(Resource::Metal), 15.0, epsilon = 1.0);
+        assert_abs_diff_eq!(forecast.stall_time(Resource::Energy), 0.0);
+        assert!(!forecast.report.unwrap().is_complete());
+    }
+}
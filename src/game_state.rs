@@ -2,20 +2,47 @@ use std::collections::HashMap;
 use std::error::Error;
 
 use approx::abs_diff_eq;
+use serde::{Deserialize, Serialize};
 
+use crate::activity::{Activity, UnitActivity};
+use crate::command::Command;
+use crate::feature::Feature;
+use crate::map::{MapData, Position};
+use crate::stalls::Stalls;
 use crate::unit::Unit;
 use crate::world_params::WorldParams;
 
 // State of the game
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct GameState {
     pub units: Vec<Unit>,
     pub unit_catalog: HashMap<String, Unit>,
+    pub features: Vec<Feature>,
+    pub feature_catalog: HashMap<String, Feature>,
     pub world_params: WorldParams,
+    pub map: MapData,
     pub energy: f32,
     pub metal: f32,
     last_metal_production: f32,
+    last_metal_pull: f32,
+    last_energy_pull: f32,
+    last_metal_expense: f32,
+    last_energy_expense: f32,
+    last_build_power_used: f32,
     pub wind_strength: f32,
+    pub tidal_strength: f32,
     pub time: f32,
+    // Resources lost to full storage since the start of the game
+    pub metal_wasted: f32,
+    pub energy_wasted: f32,
+    last_metal_waste: f32,
+    last_energy_waste: f32,
+    // Resource shortages since the start of the game
+    pub stalls: Stalls,
+    last_stalls: Stalls,
+    // Time every unit with build power spent on each activity. Keyed by the index in the world unit list.
+    pub activity: HashMap<usize, UnitActivity>,
+    last_activities: HashMap<usize, Activity>,
 }
 
 
@@ -26,12 +53,29 @@ impl GameState {
         GameState { 
             units: Vec::new(),
             unit_catalog: HashMap::new(),
+            features: Vec::new(),
+            feature_catalog: HashMap::new(),
             world_params,
+            map: MapData::default(),
             energy,
             metal,
             last_metal_production: 0.0,
+            last_metal_pull: 0.0,
+            last_energy_pull: 0.0,
+            last_metal_expense: 0.0,
+            last_energy_expense: 0.0,
+            last_build_power_used: 0.0,
             wind_strength: 25.0,
+            tidal_strength: 0.0,
             time: 0.0,
+            metal_wasted: 0.0,
+            energy_wasted: 0.0,
+            last_metal_waste: 0.0,
+            last_energy_waste: 0.0,
+            stalls: Stalls::default(),
+            last_stalls: Stalls::default(),
+            activity: HashMap::new(),
+            last_activities: HashMap::new(),
         }
     }
 
@@ -47,19 +91,92 @@ impl GameState {
     
     // Adds a unit by copying its template.
     // The unit must first be registered using `register_unit`. 
+    // Units that need geothermal are placed on the first free vent.
     pub fn add_unit(&mut self, unit_name: &str) -> Result<usize, Box<dyn Error>> {
         let unit_template = self.unit_catalog.get(unit_name).ok_or(format!("'{}' is not a known unit.", unit_name))?;
-        self.units.push(unit_template.clone());
+        let mut unit = unit_template.clone();
+        if unit.needs_geo {
+            let vent = self.free_geo_vent().ok_or(format!("No free geothermal vent to place '{}' on.", unit_name))?;
+            unit.geo_vent = Some(vent);
+        }
+        self.units.push(unit);
         Ok(self.units.len() - 1)
     }
 
 
+    // Returns the index of a geothermal vent that is not yet occupied by a unit.
+    pub fn free_geo_vent(&self) -> Option<usize> {
+        (0..self.map.geo_vents.len()).find(|vent| {
+            !self.units.iter().any(|unit| unit.geo_vent == Some(*vent))
+        })
+    }
+
+
+    // Destroys the unit. Completed units leave their corpse behind as a reclaimable feature,
+    // or a default wreck if they do not define one.
+    // The unit stays in the unit list so that indices into it remain valid.
+    pub fn destroy_unit(&mut self, unit_idx: usize) -> Result<Option<usize>, Box<dyn Error>> {
+        let unit = self.units.get_mut(unit_idx).ok_or(format!("Unit {} does not exist.", unit_idx))?;
+        let wreck = match unit.alive {
+            true => {
+                let mut feature = unit.corpse.clone().unwrap_or_else(|| Feature::default_wreck(unit.m_build_cost));
+                feature.position = unit.position;
+                Some(feature)
+            }
+            false => None,
+        };
+
+        unit.alive = false;
+        unit.metal = 0.0;
+        unit.energy = 0.0;
+        unit.build_target = None;
+        unit.reclaim_target = None;
+        unit.commands.clear();
+        unit.geo_vent = None;
+        for builder in self.units.iter_mut() {
+            if builder.build_target == Some(unit_idx) {
+                builder.build_target = None;
+            }
+        }
+
+        Ok(wreck.map(|feature| {
+            self.features.push(feature);
+            self.features.len() - 1
+        }))
+    }
+
+
+    // Use the wind and tidal strength of the map.
+    pub fn set_map(&mut self, map: MapData) {
+        self.wind_strength = map.average_wind();
+        self.tidal_strength = map.tidal_strength;
+        self.map = map;
+    }
+
+
     // Make the unit available under this name
-    pub fn register_unit(&mut self, name: &str, unit: Unit) {
+    pub fn register_unit(&mut self, name: &str, mut unit: Unit) {
+        unit.def_name = name.to_string();
         self.unit_catalog.insert(name.to_string(), unit);
     }
 
 
+    // Make the feature available under this name
+    pub fn register_feature(&mut self, name: &str, feature: Feature) {
+        self.feature_catalog.insert(name.to_string(), feature);
+    }
+
+
+    // Places a feature on the map by copying its template.
+    pub fn add_feature(&mut self, feature_name: &str, position: Option<Position>) -> Result<usize, Box<dyn Error>> {
+        let feature_template = self.feature_catalog.get(feature_name).ok_or(format!("'{}' is not a known feature.", feature_name))?;
+        let mut feature = feature_template.clone();
+        feature.position = position;
+        self.features.push(feature);
+        Ok(self.features.len() - 1)
+    }
+
+
     pub fn simulate(&mut self, dt: f32) {
         // Energy and metal production
         self.energy += dt * self.energy_production();
@@ -74,36 +191,93 @@ impl GameState {
         // We will try to imitate the system where the energy is allocated in a binary fashion.
         // It also seems that the order things are built matters, so the fact that an arbitrary unit will be preferred
         // over other ones due to it's iteration order is intended behavior.
+        // Requested and spent resources are summed up over the timestep.
+        let (mut m_pull, mut e_pull, mut m_expense, mut e_expense, mut build_power_used) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        let mut stalls = Stalls::default();
+        let mut activities: HashMap<usize, Activity> = HashMap::new();
+        let mut unit_build_power_used: HashMap<usize, f32> = HashMap::new();
+        let positions: Vec<Option<Position>> = self.units.iter().map(|unit| unit.position).collect();
+        for (unit_idx, unit) in self.units.iter().enumerate() {
+            if unit.alive && (unit.buildpower > 0.0 || unit.reclaim_speed > 0.0) {
+                activities.insert(unit_idx, Activity::Idle);
+            }
+        }
         self.last_metal_production = 0.0;
-        for unit in &self.units {
+        for (unit_idx, unit) in self.units.iter().enumerate() {
             if unit.alive {
                 let e_consumed = dt * unit.e_cost_per_second;
-                if self.energy > e_consumed {
+                e_pull += e_consumed;
+                // Units without upkeep keep working when the energy storage is empty.
+                if e_consumed <= 0.0 || self.energy > e_consumed {
                     self.energy -= e_consumed;
+                    e_expense += e_consumed;
                     // Do things that powered units do, like produce metal.
                     self.last_metal_production += unit.m_per_second;
+                } else {
+                    stalls.energy_stall_time = dt;
+                    stalls.starved_units.insert(unit_idx, dt);
                 }
             }
         }
         self.metal += dt * self.last_metal_production;
 
+        self.execute_commands(dt);
+
         // Assign build power
         // We need to use index-based loops since we are modifying the contents of elements different to the one we are looped over.
         for i in 0..self.units.len() {
             if let Some(target_idx) = self.units[i].build_target && self.units[i].alive {
+                // Another builder may have finished the target earlier in this timestep.
+                if self.units[target_idx].alive {
+                    self.units[i].build_target = None;
+                    continue;
+                }
+                // The builder has to walk into range before it can contribute.
+                if let Some(target_pos) = self.units[target_idx].position
+                    && !self.approach(i, target_pos, self.units[i].build_distance, dt) {
+                    activities.insert(i, Activity::Moving);
+                    continue;
+                }
                 // This unit is building something
                 // The percentage of the target to build in this timestep
-                let mut build_step = dt * self.units[i].buildpower / self.units[target_idx].buildtime;
+                let full_step = dt * self.units[i].buildpower / self.units[target_idx].buildtime;
                 let remaining = 1.0 - self.units[target_idx].metal / self.units[target_idx].m_build_cost;
-                build_step = build_step.min(remaining);
+                let build_step = full_step.min(remaining);
                 
                 let build_m_cost = build_step * self.units[target_idx].m_build_cost;
                 let build_e_cost = build_step * self.units[target_idx].e_build_cost;
-                if build_m_cost < self.metal && build_e_cost < self.energy {
+                m_pull += build_m_cost;
+                e_pull += build_e_cost;
+                // Steps that cost nothing of a resource do not need any of it in storage.
+                let enough_metal = build_m_cost <= 0.0 || build_m_cost < self.metal;
+                let enough_energy = build_e_cost <= 0.0 || build_e_cost < self.energy;
+                if enough_metal && enough_energy {
                     self.metal -= build_m_cost;
                     self.energy -= build_e_cost;
                     self.units[target_idx].metal += build_m_cost;
                     self.units[target_idx].energy += build_e_cost;
+                    m_expense += build_m_cost;
+                    e_expense += build_e_cost;
+                    if full_step > 0.0 {
+                        let used = self.units[i].buildpower * build_step / full_step;
+                        build_power_used += used;
+                        unit_build_power_used.insert(i, dt * used);
+                    }
+                    activities.insert(i, Activity::Building(target_idx));
+                } else {
+                    // The builder waits for resources, so the step is skipped and cannot complete the unit.
+                    if !enough_metal {
+                        stalls.metal_stall_time = dt;
+                    }
+                    if !enough_energy {
+                        stalls.energy_stall_time = dt;
+                    }
+                    if full_step > 0.0 {
+                        stalls.build_power_lost += dt * self.units[i].buildpower * build_step / full_step;
+                    }
+                    stalls.starved_units.insert(i, dt);
+                    activities.insert(i, Activity::Stalled(target_idx));
+                    continue;
                 }
 
                 if abs_diff_eq!(build_step, remaining) {
@@ -113,24 +287,202 @@ impl GameState {
             }
         }
 
-        // Clamp the stored resources.
+        // Reclaim features. Resources are gained gradually as the feature is reclaimed.
+        for i in 0..self.units.len() {
+            if let Some(feature_idx) = self.units[i].reclaim_target && self.units[i].alive {
+                if let Some(feature_pos) = self.features[feature_idx].position
+                    && !self.approach(i, feature_pos, self.units[i].build_distance, dt) {
+                    activities.insert(i, Activity::Moving);
+                    continue;
+                }
+                activities.insert(i, Activity::Reclaiming(feature_idx));
+                let feature = &mut self.features[feature_idx];
+                let remaining = 1.0 - feature.reclaimed;
+                let reclaim_step = (dt * self.units[i].reclaim_speed / feature.reclaim_time).min(remaining);
+                feature.reclaimed += reclaim_step;
+                self.metal += reclaim_step * feature.metal;
+                self.energy += reclaim_step * feature.energy;
+
+                if abs_diff_eq!(reclaim_step, remaining) {
+                    feature.reclaimed = 1.0;
+                    self.units[i].reclaim_target = None;
+                }
+            }
+        }
+
+        // Clamp the stored resources. Everything above the storage limit is wasted.
         let max_metal = self.metal_storage();
         let max_energy = self.energy_storage();
-        self.metal = self.metal.min(max_metal);
-        self.energy = self.energy.min(max_energy);
+        let m_waste = (self.metal - max_metal).max(0.0);
+        let e_waste = (self.energy - max_energy).max(0.0);
+        self.metal -= m_waste;
+        self.energy -= e_waste;
+        self.metal_wasted += m_waste;
+        self.energy_wasted += e_waste;
+
+        if dt > 0.0 {
+            self.last_metal_pull = m_pull / dt;
+            self.last_energy_pull = e_pull / dt;
+            self.last_metal_expense = m_expense / dt;
+            self.last_energy_expense = e_expense / dt;
+            self.last_build_power_used = build_power_used;
+            self.last_metal_waste = m_waste / dt;
+            self.last_energy_waste = e_waste / dt;
+        }
+        self.stalls.add(&stalls);
+        self.last_stalls = stalls;
+
+        // Units that did nothing else but changed their position followed a move command.
+        for (unit_idx, activity) in activities.iter_mut() {
+            if *activity == Activity::Idle && self.units[*unit_idx].position != positions[*unit_idx] {
+                *activity = Activity::Moving;
+            }
+            let used = unit_build_power_used.get(unit_idx).copied().unwrap_or_default();
+            self.activity.entry(*unit_idx).or_default().add(*activity, dt, used);
+        }
+        self.last_activities = activities;
 
         self.time += dt;
     }
 
 
+    // Start idle units on the next command in their queue.
+    fn execute_commands(&mut self, dt: f32) {
+        for i in 0..self.units.len() {
+            if !self.units[i].alive || self.units[i].build_target.is_some() || self.units[i].reclaim_target.is_some() {
+                continue;
+            }
+            let Some(command) = self.units[i].commands.front().cloned() else {
+                continue;
+            };
+            match command {
+                Command::Move(target) => {
+                    if self.approach(i, target, 0.0, dt) {
+                        self.units[i].commands.pop_front();
+                    }
+                }
+                Command::Build { unit, position } => {
+                    let site = position.or_else(|| self.geo_site(&unit));
+                    // The vent may be out of range of an immobile builder.
+                    if let Some(site) = site && !self.can_reach(i, site, self.units[i].build_distance) {
+                        self.units[i].commands.pop_front();
+                        continue;
+                    }
+                    if let Some(site) = site && !self.approach(i, site, self.units[i].build_distance, dt) {
+                        continue;
+                    }
+                    self.units[i].commands.pop_front();
+                    // Commands that became impossible, e.g. because the vent was taken in the meantime, are dropped.
+                    let _ = self.build_unit_at(i, &unit, site);
+                }
+                Command::Reclaim(feature_idx) => {
+                    self.units[i].commands.pop_front();
+                    let _ = self.reclaim_feature(i, feature_idx);
+                }
+            }
+        }
+    }
+
+
+    // Moves the unit towards the target for one timestep. Returns true once the unit is within range of the target.
+    // Units without a position are always considered to be in range.
+    fn approach(&mut self, unit_idx: usize, target: Position, range: f32, dt: f32) -> bool {
+        let unit = &mut self.units[unit_idx];
+        let Some(position) = unit.position else {
+            return true;
+        };
+        let distance = position.distance(&target);
+        if distance <= range {
+            return true;
+        }
+        let step = dt * unit.speed;
+        unit.position = Some(position.towards(&target, step.min(distance - range)));
+        step >= distance - range
+    }
+
+
+    // Position of the vent that a geothermal unit would be placed on.
+    fn geo_site(&self, unit_name: &str) -> Option<Position> {
+        if !self.unit_catalog.get(unit_name)?.needs_geo {
+            return None;
+        }
+        self.free_geo_vent().map(|vent| self.map.geo_vents[vent])
+    }
+
+
+    // Time in seconds the unit needs to walk within range of the target in a straight line.
+    // Infinite if the unit cannot move and is out of range.
+    pub fn travel_time(&self, unit_idx: usize, target: Position, range: f32) -> f32 {
+        let unit = &self.units[unit_idx];
+        match unit.position {
+            Some(position) => {
+                let distance = (position.distance(&target) - range).max(0.0);
+                if distance == 0.0 { 0.0 } else if unit.speed > 0.0 { distance / unit.speed } else { f32::INFINITY }
+            }
+            None => 0.0,
+        }
+    }
+
+
+    // Whether the unit is within range of the target or can walk there.
+    pub fn can_reach(&self, unit_idx: usize, target: Position, range: f32) -> bool {
+        self.travel_time(unit_idx, target, range).is_finite()
+    }
+
+
+    // Use unit to reclaim a feature.
+    pub fn reclaim_feature(&mut self, reclaimer: usize, feature_idx: usize) -> Result<(), Box<dyn Error>> {
+        if self.units[reclaimer].reclaim_speed <= 0.0 {
+            return Err("Unit cannot reclaim.".into())
+        }
+        let feature = self.features.get(feature_idx).ok_or(format!("Feature {} does not exist.", feature_idx))?;
+        if feature.is_reclaimed() {
+            return Err(format!("Feature '{}' has already been reclaimed.", feature.name).into())
+        }
+        self.units[reclaimer].reclaim_target = Some(feature_idx);
+        Ok(())
+    }
+
+
+    // Add a command to the end of the unit's queue.
+    pub fn queue_command(&mut self, unit_idx: usize, command: Command) -> Result<(), Box<dyn Error>> {
+        if let Command::Build { unit, .. } = &command
+            && !self.units[unit_idx].build_options.contains(unit) {
+            return Err(format!("Constructor cannot build unit '{}'.", unit).into())
+        }
+        // Immobile units, like factories, would wait for commands out of their range forever.
+        let target = match &command {
+            Command::Move(position) => Some((*position, 0.0)),
+            Command::Build { position, .. } => position.map(|position| (position, self.units[unit_idx].build_distance)),
+            Command::Reclaim(feature_idx) => self.features.get(*feature_idx)
+                .and_then(|feature| feature.position)
+                .map(|position| (position, self.units[unit_idx].build_distance)),
+        };
+        if let Some((position, range)) = target && !self.can_reach(unit_idx, position, range) {
+            return Err("Unit cannot move out of its range.".into())
+        }
+        self.units[unit_idx].commands.push_back(command);
+        Ok(())
+    }
+
+
     // Use unit to build a new unit
     pub fn build_unit(&mut self, builder: usize, buildee: &str) -> Result<usize, Box<dyn Error>> {
+        self.build_unit_at(builder, buildee, None)
+    }
+
+
+    // Use unit to build a new unit at the given position.
+    // Without a position, the unit is placed on its vent or where the builder stands.
+    pub fn build_unit_at(&mut self, builder: usize, buildee: &str, position: Option<Position>) -> Result<usize, Box<dyn Error>> {
         // Make sure that the builder is allowed to build the unit
         if !self.units[builder].build_options.contains(buildee) {
             return Err(format!("Constructor cannot build unit '{}'.", buildee).into())
         }
 
         let buildee_idx = self.add_unit(buildee)?;
+        let vent_position = self.units[buildee_idx].geo_vent.map(|vent| self.map.geo_vents[vent]);
+        self.units[buildee_idx].position = vent_position.or(position).or(self.units[builder].position);
         self.units[builder].build_target = Some(buildee_idx);
         Ok(buildee_idx)
     }
@@ -164,21 +516,100 @@ impl GameState {
             if unit.alive {
                 e_prod += unit.e_per_second;
                 e_prod += unit.wind_e_per_second.min(self.wind_strength);
+                e_prod += unit.tidal_e_multiplier * self.tidal_strength;
             }
         }
         e_prod
     }
 
 
+    // Energy per second that completed units need to keep working.
+    pub fn energy_upkeep(&self) -> f32 {
+        let mut upkeep = 0.0;
+        for unit in &self.units {
+            if unit.alive {
+                upkeep += unit.e_cost_per_second;
+            }
+        }
+        upkeep
+    }
+
+
     pub fn metal_production(&self) -> f32 {
         self.last_metal_production
     }
+
+
+    // Metal per second that consumers requested during the last timestep, including requests that could not be met.
+    pub fn metal_pull(&self) -> f32 {
+        self.last_metal_pull
+    }
+
+
+    pub fn energy_pull(&self) -> f32 {
+        self.last_energy_pull
+    }
+
+
+    // Metal per second that was actually spent during the last timestep.
+    pub fn metal_expense(&self) -> f32 {
+        self.last_metal_expense
+    }
+
+
+    pub fn energy_expense(&self) -> f32 {
+        self.last_energy_expense
+    }
+
+
+    // Metal per second that was lost to full storage during the last timestep.
+    pub fn metal_waste(&self) -> f32 {
+        self.last_metal_waste
+    }
+
+
+    pub fn energy_waste(&self) -> f32 {
+        self.last_energy_waste
+    }
+
+
+    // Resource shortages during the last timestep.
+    pub fn last_stalls(&self) -> &Stalls {
+        &self.last_stalls
+    }
+
+
+    // What the unit did during the last timestep. None for units without build power and units that were not alive.
+    pub fn last_activity(&self, unit_idx: usize) -> Option<Activity> {
+        self.last_activities.get(&unit_idx).copied()
+    }
+
+
+    // Total build power of all completed units.
+    pub fn build_power(&self) -> f32 {
+        let mut power = 0.0;
+        for unit in &self.units {
+            if unit.alive {
+                power += unit.buildpower;
+            }
+        }
+        power
+    }
+
+
+    // Build power that went into construction during the last timestep.
+    pub fn build_power_used(&self) -> f32 {
+        self.last_build_power_used
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::map::Position;
+    use crate::activity::UtilizationReport;
+    use crate::feature::DEFAULT_WRECK_METAL;
     use approx::assert_abs_diff_eq;
 
     #[test]
@@ -414,6 +845,7 @@ mod tests {
 
 
     #[test]
+    #[allow(clippy::redundant_pattern_matching)]
     fn test_build_unit() {
         // Create the world
         let mut state = GameState::new(WorldParams::default());
@@ -454,4 +886,502 @@ mod tests {
         assert_abs_diff_eq!(state.energy, 500.0 - 175.0 * 0.5);
         assert_abs_diff_eq!(state.metal, 500.0 - 40.0 * 0.5);
     }
-}
\ No newline at end of file
+
+
+    #[test]
+    fn test_geothermal() {
+        let mut state = GameState::new(WorldParams::default());
+        state.map.geo_vents.push(Position::new(100.0, 200.0));
+        state.energy = 500.0;
+        state.metal = 500.0;
+
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 300.0;
+        com.build_options.insert("geo".to_string());
+        state.register_unit("commander", com);
+
+        let mut geo = Unit::new_unconstructed(50.0, 100.0, 300.0);
+        geo.e_per_second = 300.0;
+        geo.e_storage = 1000.0;
+        geo.needs_geo = true;
+        state.register_unit("geo", geo);
+
+        let com_idx = state.add_completed_unit("commander").unwrap();
+        let geo_idx = state.build_unit(com_idx, "geo").unwrap();
+        assert_eq!(state.units[geo_idx].geo_vent, Some(0));
+        assert_eq!(state.free_geo_vent(), None);
+
+        // The only vent is taken, so a second geo cannot be placed.
+        let err = state.build_unit(com_idx, "geo");
+        assert!(err.is_err());
+        assert_eq!(state.units.len(), 2);
+
+        // Takes one second to build
+        state.simulate(1.0 + 1e-6);
+        assert!(state.units[geo_idx].alive);
+        assert_abs_diff_eq!(state.energy, 400.0);
+        assert_abs_diff_eq!(state.metal, 450.0);
+
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.energy_production(), 300.0);
+        assert_abs_diff_eq!(state.energy, 700.0);
+    }
+
+
+    #[test]
+    fn test_map() {
+        let mut state = GameState::new(WorldParams::default());
+        let map = MapData { min_wind: 6.0, max_wind: 12.0, tidal_strength: 15.0, ..MapData::default() };
+        state.set_map(map);
+        assert_abs_diff_eq!(state.wind_strength, 9.0);
+        assert_abs_diff_eq!(state.tidal_strength, 15.0);
+
+        let mut tidal = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        tidal.tidal_e_multiplier = 1.0;
+        state.register_unit("tidal", tidal);
+        state.add_completed_unit("tidal").unwrap();
+        assert_abs_diff_eq!(state.energy_production(), 15.0);
+    }
+
+
+    #[test]
+    fn test_walk_to_build_site() {
+        let mut state = GameState::new(WorldParams::default());
+        state.wind_strength = 20.0;
+
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 300.0;
+        com.speed = 50.0;
+        com.build_distance = 100.0;
+        com.position = Some(Position::new(0.0, 0.0));
+        com.build_options.insert("wind".to_string());
+        state.register_unit("commander", com);
+
+        let mut wind = Unit::new_unconstructed(40.0, 175.0, 1600.0);
+        wind.wind_e_per_second = 25.0;
+        state.register_unit("wind", wind);
+
+        let com_idx = state.add_completed_unit("commander").unwrap();
+        let site = Position::new(600.0, 0.0);
+        assert_abs_diff_eq!(state.travel_time(com_idx, site, 100.0), 10.0);
+        assert!(state.queue_command(com_idx, Command::Build { unit: "commander".to_string(), position: None }).is_err());
+        state.queue_command(com_idx, Command::Build { unit: "wind".to_string(), position: Some(site) }).unwrap();
+
+        // Walking 500 elmos takes 10 seconds, during which nothing is built.
+        for _ in 0..99 {
+            state.simulate(0.1);
+        }
+        assert_eq!(state.units.len(), 1);
+        assert_abs_diff_eq!(state.units[0].position.unwrap().x, 495.0, epsilon = 1e-3);
+
+        state.simulate(0.1);
+        state.simulate(0.1);
+        assert_eq!(state.units.len(), 2);
+        assert_eq!(state.units[1].position, Some(site));
+        assert_eq!(state.units[0].build_target, Some(1));
+        assert!(state.units[0].commands.is_empty());
+
+        // The wind takes 5.333 seconds to build once the commander is in range.
+        for _ in 0..55 {
+            state.simulate(0.1);
+        }
+        assert!(state.units[1].alive);
+        assert_eq!(state.units[0].build_target, None);
+    }
+
+
+    #[test]
+    fn test_move_command() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.speed = 40.0;
+        com.position = Some(Position::new(0.0, 0.0));
+        state.register_unit("commander", com);
+        let com_idx = state.add_completed_unit("commander").unwrap();
+
+        state.queue_command(com_idx, Command::Move(Position::new(30.0, 40.0))).unwrap();
+        state.simulate(1.0);
+        assert_eq!(state.units[0].commands.len(), 1);
+        assert_abs_diff_eq!(state.units[0].position.unwrap().x, 24.0, epsilon = 1e-4);
+        assert_abs_diff_eq!(state.units[0].position.unwrap().z, 32.0, epsilon = 1e-4);
+        state.simulate(1.0);
+        assert_eq!(state.units[0].position, Some(Position::new(30.0, 40.0)));
+        assert!(state.units[0].commands.is_empty());
+    }
+
+
+    #[test]
+    fn test_immobile_builder() {
+        let mut state = GameState::new(WorldParams::default());
+        state.map.geo_vents = vec![Position::new(1000.0, 0.0)];
+        let mut lab = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        lab.buildpower = 100.0;
+        lab.build_distance = 100.0;
+        lab.position = Some(Position::new(0.0, 0.0));
+        for option in ["wind", "geo"] {
+            lab.build_options.insert(option.to_string());
+        }
+        state.register_unit("lab", lab);
+        state.register_unit("wind", Unit::new_unconstructed(10.0, 10.0, 100.0));
+        let mut geo = Unit::new_unconstructed(10.0, 10.0, 100.0);
+        geo.needs_geo = true;
+        state.register_unit("geo", geo);
+        let lab_idx = state.add_completed_unit("lab").unwrap();
+
+        assert!(state.travel_time(lab_idx, Position::new(500.0, 0.0), 100.0).is_infinite());
+        assert!(state.queue_command(lab_idx, Command::Move(Position::new(500.0, 0.0))).is_err());
+        let far = Some(Position::new(500.0, 0.0));
+        assert!(state.queue_command(lab_idx, Command::Build { unit: "wind".to_string(), position: far }).is_err());
+        assert!(state.units[lab_idx].commands.is_empty());
+
+        // The vent is only known once the command is executed. It is dropped instead of blocking the queue.
+        state.queue_command(lab_idx, Command::Build { unit: "geo".to_string(), position: None }).unwrap();
+        let near = Some(Position::new(50.0, 0.0));
+        state.queue_command(lab_idx, Command::Build { unit: "wind".to_string(), position: near }).unwrap();
+        state.simulate(0.5);
+        state.simulate(0.5);
+        assert!(state.units[lab_idx].commands.is_empty());
+        assert_eq!(state.units.len(), 2);
+        assert_eq!(state.units[1].def_name, "wind");
+    }
+
+
+    #[test]
+    fn test_reclaim() {
+        let mut state = GameState::new(WorldParams::default());
+        state.metal = 100.0;
+        state.energy = 100.0;
+
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.reclaim_speed = 100.0;
+        com.speed = 25.0;
+        com.build_distance = 100.0;
+        com.position = Some(Position::new(0.0, 0.0));
+        state.register_unit("commander", com);
+        state.register_feature("rock", Feature::new(30.0, 0.0, 200.0));
+        state.register_feature("tree", Feature::new(0.0, 600.0, 200.0));
+
+        let com_idx = state.add_completed_unit("commander").unwrap();
+        let rock = state.add_feature("rock", Some(Position::new(150.0, 0.0))).unwrap();
+        let tree = state.add_feature("tree", Some(Position::new(100.0, 0.0))).unwrap();
+        state.queue_command(com_idx, Command::Reclaim(rock)).unwrap();
+        state.queue_command(com_idx, Command::Reclaim(tree)).unwrap();
+
+        // Walk into range for two seconds, then reclaim the rock for two seconds.
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.metal, 100.0);
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.features[rock].reclaimed, 0.5);
+        assert_abs_diff_eq!(state.metal, 115.0);
+        state.simulate(1.0);
+        assert!(state.features[rock].is_reclaimed());
+        assert_abs_diff_eq!(state.metal, 130.0);
+        assert_eq!(state.units[0].reclaim_target, None);
+        assert!(state.reclaim_feature(com_idx, rock).is_err());
+
+        // The tree is already in range. Reclaimed energy is limited by storage.
+        state.simulate(2.0);
+        assert!(state.features[tree].is_reclaimed());
+        assert_abs_diff_eq!(state.energy, 500.0);
+    }
+
+
+    #[test]
+    fn test_destroy_unit() {
+        let mut state = GameState::new(WorldParams::default());
+        state.map.geo_vents.push(Position::new(0.0, 0.0));
+
+        let mut geo = Unit::new_unconstructed(560.0, 13000.0, 13100.0);
+        geo.e_per_second = 300.0;
+        geo.needs_geo = true;
+        geo.corpse = Some(Feature::new(300.0, 0.0, 1800.0));
+        geo.position = Some(Position::new(10.0, 20.0));
+        state.register_unit("geo", geo);
+
+        let geo_idx = state.add_completed_unit("geo").unwrap();
+        let wreck = state.destroy_unit(geo_idx).unwrap().unwrap();
+        assert!(!state.units[geo_idx].alive);
+        assert_abs_diff_eq!(state.energy_production(), 0.0);
+        assert_abs_diff_eq!(state.features[wreck].metal, 300.0);
+        assert_eq!(state.features[wreck].position, Some(Position::new(10.0, 20.0)));
+
+        // The vent can be used again, but an unfinished unit leaves no wreck.
+        assert_eq!(state.free_geo_vent(), Some(0));
+        let frame_idx = state.add_unit("geo").unwrap();
+        assert_eq!(state.destroy_unit(frame_idx).unwrap(), None);
+        assert_eq!(state.features.len(), 1);
+
+        // Units without a corpse leave a default wreck.
+        let mut wind = Unit::new_unconstructed(40.0, 175.0, 1600.0);
+        wind.position = Some(Position::new(50.0, 0.0));
+        state.register_unit("wind", wind);
+        let wind_idx = state.add_completed_unit("wind").unwrap();
+        let wreck = state.destroy_unit(wind_idx).unwrap().unwrap();
+        assert_abs_diff_eq!(state.features[wreck].metal, 40.0 * DEFAULT_WRECK_METAL);
+        assert_abs_diff_eq!(state.features[wreck].energy, 0.0);
+        assert_eq!(state.features[wreck].position, Some(Position::new(50.0, 0.0)));
+    }
+
+
+    #[test]
+    fn test_pull_and_expense() {
+        let mut state = GameState::new(WorldParams::default());
+        state.metal = 500.0;
+        state.energy = 500.0;
+
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 300.0;
+        com.build_options.insert("wind".to_string());
+        state.register_unit("commander", com);
+        let mut mex = Unit::new_unconstructed(50.0, 500.0, 1800.0);
+        mex.e_cost_per_second = 3.0;
+        state.register_unit("mex", mex);
+        state.register_unit("wind", Unit::new_unconstructed(40.0, 175.0, 1600.0));
+
+        state.add_completed_unit("mex").unwrap();
+        let com_idx = state.add_completed_unit("commander").unwrap();
+        state.build_unit(com_idx, "wind").unwrap();
+
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.metal_pull(), 40.0 * 300.0 / 1600.0);
+        assert_abs_diff_eq!(state.energy_pull(), 3.0 + 175.0 * 300.0 / 1600.0);
+        assert_abs_diff_eq!(state.metal_expense(), state.metal_pull());
+        assert_abs_diff_eq!(state.energy_expense(), state.energy_pull());
+        assert_abs_diff_eq!(state.build_power(), 300.0);
+        assert_abs_diff_eq!(state.build_power_used(), 300.0);
+
+        // Without metal, the commander still pulls but nothing is spent.
+        state.metal = 0.0;
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.metal_pull(), 40.0 * 300.0 / 1600.0);
+        assert_abs_diff_eq!(state.metal_expense(), 0.0);
+        assert_abs_diff_eq!(state.energy_expense(), 3.0);
+        assert_abs_diff_eq!(state.build_power_used(), 0.0);
+    }
+
+
+    #[test]
+    fn test_waste() {
+        let mut state = GameState::new(WorldParams::default());
+        state.metal = 490.0;
+        state.energy = 0.0;
+
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.e_per_second = 30.0;
+        com.m_per_second = 2.0;
+        state.register_unit("commander", com);
+        state.add_completed_unit("commander").unwrap();
+
+        state.simulate(2.0);
+        assert_abs_diff_eq!(state.metal, 494.0);
+        assert_abs_diff_eq!(state.metal_waste(), 0.0);
+        assert_abs_diff_eq!(state.metal_wasted, 0.0);
+
+        // 6 metal fit into storage, the other 4 overflow.
+        state.simulate(5.0);
+        assert_abs_diff_eq!(state.metal, 500.0);
+        assert_abs_diff_eq!(state.metal_waste(), 0.8);
+        assert_abs_diff_eq!(state.metal_wasted, 4.0);
+
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.metal_waste(), 2.0);
+        assert_abs_diff_eq!(state.metal_wasted, 6.0);
+        assert_abs_diff_eq!(state.energy_wasted, 0.0);
+    }
+
+
+    #[test]
+    fn test_stalls() {
+        let mut state = GameState::new(WorldParams::default());
+        state.metal = 30.0;
+        state.energy = 4.0;
+
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 100.0;
+        com.m_per_second = 5.0;
+        com.build_options.insert("wind".to_string());
+        state.register_unit("commander", com);
+        let mut mex = Unit::new_unconstructed(50.0, 500.0, 1800.0);
+        mex.e_cost_per_second = 3.0;
+        mex.m_per_second = 3.0;
+        state.register_unit("mex", mex);
+        state.register_unit("wind", Unit::new_unconstructed(40.0, 0.0, 100.0));
+
+        let mex_idx = state.add_completed_unit("mex").unwrap();
+        let com_idx = state.add_completed_unit("commander").unwrap();
+        let wind_idx = state.build_unit(com_idx, "wind").unwrap();
+
+        // The mex gets energy, the wind needs 40 metal but only 38 are there.
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.last_stalls().energy_stall_time, 0.0);
+        assert_abs_diff_eq!(state.last_stalls().metal_stall_time, 1.0);
+        assert_abs_diff_eq!(state.last_stalls().build_power_lost, 100.0);
+        assert_eq!(state.last_stalls().most_starved(), vec![(com_idx, 1.0)]);
+        assert!(!state.units[wind_idx].alive);
+
+        // Now the mex runs out of energy, but the commander produces metal without upkeep.
+        // The wind needs no energy, so it can be finished.
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.last_stalls().energy_stall_time, 1.0);
+        assert_abs_diff_eq!(state.last_stalls().metal_stall_time, 0.0);
+        assert_eq!(state.last_stalls().most_starved(), vec![(mex_idx, 1.0)]);
+        assert_abs_diff_eq!(state.metal_production(), 5.0);
+        assert!(state.units[wind_idx].alive);
+
+        assert_abs_diff_eq!(state.stalls.metal_stall_time, 1.0);
+        assert_abs_diff_eq!(state.stalls.energy_stall_time, 1.0);
+        assert_abs_diff_eq!(state.stalls.build_power_lost, 100.0);
+        assert_eq!(state.stalls.most_starved(), vec![(mex_idx, 1.0), (com_idx, 1.0)]);
+    }
+
+
+    #[test]
+    fn test_activity() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 100.0;
+        com.speed = 100.0;
+        com.build_options.insert("wind".to_string());
+        state.register_unit("commander", com);
+        state.register_unit("wind", Unit::new_unconstructed(20.0, 0.0, 200.0));
+        let com_idx = state.add_completed_unit("commander").unwrap();
+        state.units[com_idx].position = Some(Position::new(0.0, 0.0));
+
+        state.simulate(1.0);
+        assert_eq!(state.last_activity(com_idx), Some(Activity::Idle));
+
+        let wind_idx = state.build_unit(com_idx, "wind").unwrap();
+        state.simulate(1.0);
+        state.simulate(1.0);
+        assert_eq!(state.last_activity(com_idx), Some(Activity::Building(wind_idx)));
+        assert!(state.units[wind_idx].alive);
+        assert_eq!(state.last_activity(wind_idx), None);
+
+        state.queue_command(com_idx, Command::Move(Position::new(100.0, 0.0))).unwrap();
+        state.simulate(1.0);
+        assert_eq!(state.last_activity(com_idx), Some(Activity::Moving));
+
+        state.metal = 0.0;
+        let wind_idx = state.build_unit(com_idx, "wind").unwrap();
+        state.simulate(1.0);
+        assert_eq!(state.last_activity(com_idx), Some(Activity::Stalled(wind_idx)));
+
+        let activity = &state.activity[&com_idx];
+        assert_abs_diff_eq!(activity.idle_time, 1.0);
+        assert_abs_diff_eq!(activity.moving_time, 1.0);
+        assert_abs_diff_eq!(activity.stalled_time, 1.0);
+        assert_abs_diff_eq!(activity.productive_time, 2.0);
+        assert_abs_diff_eq!(activity.build_power_used, 200.0);
+        assert_abs_diff_eq!(activity.utilization(100.0), 0.4);
+
+        let report = UtilizationReport::from_state(&state);
+        assert_eq!(report.rows.len(), 1);
+        assert_eq!(report.rows[0].name, "commander");
+        assert_abs_diff_eq!(report.utilization(), 0.4);
+        assert_abs_diff_eq!(report.idle_time_by_name()["commander"], 1.0);
+    }
+
+
+    #[test]
+    fn test_upkeep_without_energy() {
+        let mut state = GameState::new(WorldParams::default());
+        state.energy = 0.0;
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.m_per_second = 2.0;
+        state.register_unit("commander", com);
+        let mut mex = Unit::new_unconstructed(50.0, 500.0, 1800.0);
+        mex.m_per_second = 3.0;
+        mex.e_cost_per_second = 3.0;
+        state.register_unit("mex", mex);
+        state.add_completed_unit("commander").unwrap();
+        state.add_completed_unit("mex").unwrap();
+
+        // Only the mex needs energy to work.
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.metal_production(), 2.0);
+        assert_abs_diff_eq!(state.last_stalls().energy_stall_time, 1.0);
+    }
+
+
+    #[test]
+    fn test_build_without_energy() {
+        let mut state = GameState::new(WorldParams::default());
+        state.metal = 100.0;
+        state.energy = 0.0;
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 100.0;
+        com.build_options.insert("wall".to_string());
+        com.build_options.insert("wind".to_string());
+        state.register_unit("commander", com);
+        state.register_unit("wall", Unit::new_unconstructed(10.0, 0.0, 200.0));
+        state.register_unit("wind", Unit::new_unconstructed(10.0, 10.0, 200.0));
+        let com_idx = state.add_completed_unit("commander").unwrap();
+
+        // A unit that costs no energy can be built with an empty energy storage.
+        let wall_idx = state.build_unit(com_idx, "wall").unwrap();
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.units[wall_idx].metal, 5.0);
+        state.simulate(1.0);
+        assert!(state.units[wall_idx].alive);
+
+        let wind_idx = state.build_unit(com_idx, "wind").unwrap();
+        state.simulate(1.0);
+        assert_abs_diff_eq!(state.units[wind_idx].metal, 0.0);
+        assert_abs_diff_eq!(state.last_stalls().energy_stall_time, 1.0);
+    }
+
+
+    #[test]
+    fn test_unaffordable_last_step() {
+        let mut state = GameState::new(WorldParams::default());
+        state.metal = 8.0;
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 100.0;
+        com.build_options.insert("wind".to_string());
+        state.register_unit("commander", com);
+        state.register_unit("wind", Unit::new_unconstructed(10.0, 0.0, 100.0));
+        let com_idx = state.add_completed_unit("commander").unwrap();
+        let wind_idx = state.build_unit(com_idx, "wind").unwrap();
+
+        // The wind would be finished in one step, but there is not enough metal to pay for it.
+        state.simulate(1.0);
+        assert!(!state.units[wind_idx].alive);
+        assert_abs_diff_eq!(state.units[wind_idx].metal, 0.0);
+        assert_eq!(state.units[com_idx].build_target, Some(wind_idx));
+
+        state.metal = 20.0;
+        state.simulate(1.0);
+        assert!(state.units[wind_idx].alive);
+        assert_abs_diff_eq!(state.metal, 10.0);
+    }
+
+
+    #[test]
+    fn test_target_finished_by_other_builder() {
+        let mut state = GameState::new(WorldParams::default());
+        state.metal = 100.0;
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 100.0;
+        com.build_distance = 100.0;
+        com.speed = 10.0;
+        com.position = Some(Position::new(0.0, 0.0));
+        com.build_options.insert("wind".to_string());
+        state.register_unit("commander", com);
+        state.register_unit("wind", Unit::new_unconstructed(10.0, 0.0, 100.0));
+        let com_idx = state.add_completed_unit("commander").unwrap();
+        let helper_idx = state.add_completed_unit("commander").unwrap();
+        state.units[helper_idx].position = Some(Position::new(500.0, 0.0));
+
+        // The commander finishes the wind in a single step, before the helper gets its turn.
+        let wind_idx = state.build_unit(com_idx, "wind").unwrap();
+        state.units[helper_idx].build_target = Some(wind_idx);
+        state.simulate(1.0);
+        assert!(state.units[wind_idx].alive);
+        assert_abs_diff_eq!(state.metal, 90.0);
+
+        // The helper does not build the wind a second time, nor walk over to it.
+        assert_eq!(state.units[helper_idx].build_target, None);
+        assert_eq!(state.units[helper_idx].position, Some(Position::new(500.0, 0.0)));
+        assert_eq!(state.last_activity(helper_idx), Some(Activity::Idle));
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::activity::Activity;
+use crate::charts::escape;
+use crate::game_state::GameState;
+use crate::recorder::Observer;
+
+// What a builder is shown doing on the chart.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Task {
+    Idle,
+    Moving,
+    // Building a unit that the builder started itself.
+    Building(usize),
+    // Helping to build a unit that another builder started.
+    Assisting(usize),
+    // Waiting for resources to build the unit.
+    Waiting(usize),
+    Reclaiming(usize),
+}
+
+
+impl Task {
+    // Character used for the task in the text chart.
+    pub fn symbol(&self) -> char {
+        match self {
+            Task::Idle => '.',
+            Task::Moving => '>',
+            Task::Building(_) => '#',
+            Task::Assisting(_) => '+',
+            Task::Waiting(_) => '!',
+            Task::Reclaiming(_) => 'r',
+        }
+    }
+
+
+    fn color(&self) -> &'static str {
+        match self {
+            Task::Idle => "#dddddd",
+            Task::Moving => "#9ecae1",
+            Task::Building(_) => "#31a354",
+            Task::Assisting(_) => "#a1d99b",
+            Task::Waiting(_) => "#de2d26",
+            Task::Reclaiming(_) => "#fdae6b",
+        }
+    }
+}
+
+
+// A stretch of time during which a builder did the same thing.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Segment {
+    pub start: f32,
+    pub end: f32,
+    pub task: Task,
+    // Name of the unit or feature that the task is about, empty for idling and moving.
+    pub label: String,
+}
+
+
+// Timeline of what every builder did over a simulation.
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    // Segments of each builder, keyed by the index in the world unit list.
+    pub rows: BTreeMap<usize, Vec<Segment>>,
+    pub names: HashMap<usize, String>,
+    pub end_time: f32,
+    // The first builder that worked on each unit.
+    owners: HashMap<usize, usize>,
+}
+
+
+impl Timeline {
+    pub fn new() -> Timeline {
+        Timeline::default()
+    }
+
+
+    fn task(&mut self, unit_idx: usize, activity: Activity) -> Task {
+        match activity {
+            Activity::Idle => Task::Idle,
+            Activity::Moving => Task::Moving,
+            Activity::Building(target) => match *self.owners.entry(target).or_insert(unit_idx) == unit_idx {
+                true => Task::Building(target),
+                false => Task::Assisting(target),
+            },
+            Activity::Stalled(target) => {
+                self.owners.entry(target).or_insert(unit_idx);
+                Task::Waiting(target)
+            }
+            Activity::Reclaiming(feature) => Task::Reclaiming(feature),
+        }
+    }
+
+
+    fn label(state: &GameState, task: Task) -> String {
+        match task {
+            Task::Idle | Task::Moving => String::new(),
+            Task::Building(target) | Task::Assisting(target) | Task::Waiting(target) => state.units[target].def_name.clone(),
+            Task::Reclaiming(feature) => state.features[feature].name.clone(),
+        }
+    }
+
+
+    // Text chart with one line per builder and one character per column.
+    pub fn to_text(&self, width: usize) -> String {
+        let mut text = String::new();
+        if self.end_time <= 0.0 || width == 0 {
+            return text;
+        }
+        let column_time = self.end_time / width as f32;
+        let name_width = self.rows.keys().map(|idx| self.row_name(*idx).len()).max().unwrap_or(0);
+        for (unit_idx, segments) in &self.rows {
+            let mut line = String::new();
+            for column in 0..width {
+                // Show the task in the middle of the column.
+                let time = (column as f32 + 0.5) * column_time;
+                let segment = segments.iter().find(|segment| segment.start <= time && time < segment.end);
+                line.push(segment.map_or(' ', |segment| segment.task.symbol()));
+            }
+            writeln!(text, "{:<width$} |{}|", self.row_name(*unit_idx), line, width = name_width).unwrap();
+        }
+        writeln!(text, "{:<width$} 0s{:>line$.0}s", "", self.end_time, width = name_width, line = width - 1).unwrap();
+        text.push_str("# building  + assisting  ! waiting  r reclaiming  > moving  . idle\n");
+        text
+    }
+
+
+    pub fn to_svg(&self) -> String {
+        const ROW_HEIGHT: f32 = 24.0;
+        const LABEL_WIDTH: f32 = 160.0;
+        const CHART_WIDTH: f32 = 800.0;
+        let scale = if self.end_time > 0.0 { CHART_WIDTH / self.end_time } else { 0.0 };
+        let height = ROW_HEIGHT * (self.rows.len() + 1) as f32;
+
+        let mut svg = String::new();
+        writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#,
+            LABEL_WIDTH + CHART_WIDTH + 20.0, height).unwrap();
+        for (row, (unit_idx, segments)) in self.rows.iter().enumerate() {
+            let y = ROW_HEIGHT * row as f32;
+            writeln!(svg, r#"<text x="4" y="{}">{}</text>"#, y + 16.0, escape(&self.row_name(*unit_idx))).unwrap();
+            for segment in segments {
+                let x = LABEL_WIDTH + scale * segment.start;
+                let width = scale * (segment.end - segment.start);
+                let title = match segment.label.is_empty() {
+                    true => format!("{:?} {:.1}s - {:.1}s", segment.task, segment.start, segment.end),
+                    false => format!("{} {:.1}s - {:.1}s", segment.label, segment.start, segment.end),
+                };
+                writeln!(svg, r#"<rect x="{:.1}" y="{}" width="{:.1}" height="{}" fill="{}"><title>{}</title></rect>"#,
+                    x, y + 2.0, width, ROW_HEIGHT - 4.0, segment.task.color(), escape(&title)).unwrap();
+                // Only label bars that are wide enough for the text.
+                if !segment.label.is_empty() && width >= 7.0 * segment.label.len() as f32 {
+                    writeln!(svg, r#"<text x="{:.1}" y="{}" fill="white">{}</text>"#, x + 3.0, y + 16.0, escape(&segment.label)).unwrap();
+                }
+            }
+        }
+
+        // Time axis with a tick every ten seconds
+        let axis_y = height - ROW_HEIGHT;
+        writeln!(svg, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="black"/>"#, LABEL_WIDTH, axis_y, LABEL_WIDTH + CHART_WIDTH, axis_y).unwrap();
+        let mut tick = 0.0;
+        while tick <= self.end_time {
+            let x = LABEL_WIDTH + scale * tick;
+            writeln!(svg, r#"<line x1="{:.1}" y1="{}" x2="{:.1}" y2="{}" stroke="black"/>"#, x, axis_y, x, axis_y + 4.0).unwrap();
+            writeln!(svg, r#"<text x="{:.1}" y="{}" text-anchor="middle">{}s</text>"#, x, axis_y + 16.0, tick).unwrap();
+            tick += 10.0;
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+
+    pub fn write_text(&self, path: &Path, width: usize) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_text(width))?;
+        Ok(())
+    }
+
+
+    pub fn write_svg(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_svg())?;
+        Ok(())
+    }
+
+
+    fn row_name(&self, unit_idx: usize) -> String {
+        format!("#{} {}", unit_idx, self.names.get(&unit_idx).map_or("", String::as_str))
+    }
+}
+
+
+impl Observer for Timeline {
+    // Adds what the builders did since the last call.
+    fn record(&mut self, state: &GameState) {
+        let start = self.end_time;
+        self.end_time = state.time;
+        if state.time <= start {
+            return;
+        }
+        for unit_idx in 0..state.units.len() {
+            let Some(activity) = state.last_activity(unit_idx) else {
+                continue;
+            };
+            let task = self.task(unit_idx, activity);
+            self.names.entry(unit_idx).or_insert_with(|| state.units[unit_idx].def_name.clone());
+            let segments = self.rows.entry(unit_idx).or_default();
+            match segments.last_mut() {
+                Some(last) if last.task == task && (last.end - start).abs() < 1e-4 => last.end = state.time,
+                _ => segments.push(Segment { start, end: state.time, task, label: Timeline::label(state, task) }),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit::Unit;
+    use crate::world_params::WorldParams;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_timeline() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 100.0;
+        com.build_options.insert("wind".to_string());
+        state.register_unit("commander", com);
+        let mut helper = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        helper.buildpower = 200.0;
+        state.register_unit("helper", helper);
+        state.register_unit("wind", Unit::new_unconstructed(20.0, 0.0, 400.0));
+        let com_idx = state.add_completed_unit("commander").unwrap();
+        let helper_idx = state.add_completed_unit("helper").unwrap();
+
+        let mut timeline = Timeline::new();
+        timeline.record(&state);
+        state.simulate(1.0);
+        timeline.record(&state);
+        let wind_idx = state.build_unit(com_idx, "wind").unwrap();
+        state.simulate(1.0);
+        timeline.record(&state);
+        state.units[helper_idx].build_target = Some(wind_idx);
+        state.simulate(1.0);
+        timeline.record(&state);
+        state.simulate(1.0);
+        timeline.record(&state);
+
+        assert_abs_diff_eq!(timeline.end_time, 4.0);
+        let com_segments = &timeline.rows[&com_idx];
+        assert_eq!(com_segments.len(), 3);
+        assert_eq!(com_segments[0].task, Task::Idle);
+        assert_eq!(com_segments[1].task, Task::Building(wind_idx));
+        assert_eq!(com_segments[1].label, "wind");
+        assert_abs_diff_eq!(com_segments[1].start, 1.0);
+        assert_abs_diff_eq!(com_segments[1].end, 3.0);
+        assert_eq!(com_segments[2].task, Task::Idle);
+
+        let helper_segments = &timeline.rows[&helper_idx];
+        assert_eq!(helper_segments[1].task, Task::Assisting(wind_idx));
+        assert_abs_diff_eq!(helper_segments[1].end, 3.0);
+
+        let text = timeline.to_text(8);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "#0 commander |..####..|");
+        assert_eq!(lines[1], "#1 helper    |....++..|");
+
+        let svg = timeline.to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<rect").count(), 6);
+    }
+}
@@ -1,5 +1,30 @@
 pub mod unit;
 pub mod loader;
 pub mod world_params;
+pub mod map;
+pub mod map_loader;
+pub mod command;
+pub mod feature;
+pub mod build_order;
+pub mod mod_options;
+pub mod scenario;
+pub mod recorder;
+pub mod stalls;
+pub mod activity;
+pub mod gantt;
+pub mod charts;
+pub mod snapshot;
+pub mod optimizer;
+pub mod evolution;
+pub mod planner;
+pub mod tech_tree;
+pub mod lint;
+pub mod catalog_diff;
+pub mod efficiency;
+pub mod forecast;
+pub mod recommender;
+pub mod monte_carlo;
 pub mod game_state;
+#[cfg(test)]
+mod fixtures;
 
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mlua::prelude::*;
+
+use crate::loader::{definition_paths, eval_definition, get_float_or, load_catalog_entry};
+use crate::unit::Unit;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Severity {
+    // The catalog cannot be simulated correctly.
+    Error,
+    // The value is allowed but probably not intended.
+    Warning,
+}
+
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => f.pad("error"),
+            Severity::Warning => f.pad("warning"),
+        }
+    }
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Issue {
+    pub severity: Severity,
+    // Key of the unit in the catalog, or the path of the definition file if it could not be loaded.
+    pub unit: String,
+    pub message: String,
+}
+
+
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct LintReport {
+    pub issues: Vec<Issue>,
+}
+
+
+impl LintReport {
+    fn add(&mut self, severity: Severity, unit: &str, message: String) {
+        self.issues.push(Issue { severity, unit: unit.to_string(), message });
+    }
+
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues.iter().filter(|issue| issue.severity == severity).count()
+    }
+
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+
+    // Errors first, then by unit.
+    fn sort(&mut self) {
+        self.issues.sort_by(|a, b| (a.severity, &a.unit, &a.message).cmp(&(b.severity, &b.unit, &b.message)));
+    }
+}
+
+
+impl fmt::Display for LintReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{:<8} {}: {}", issue.severity, issue.unit, issue.message)?;
+        }
+        write!(f, "{} error(s), {} warning(s)", self.count(Severity::Error), self.count(Severity::Warning))
+    }
+}
+
+
+// Checks the units of a loaded catalog for values that break or distort the simulation.
+pub fn lint_catalog(catalog: &HashMap<String, Unit>) -> LintReport {
+    let mut report = LintReport::default();
+    for (name, unit) in catalog {
+        let mut options: Vec<&String> = unit.build_options.iter().filter(|option| !catalog.contains_key(*option)).collect();
+        options.sort();
+        for option in options {
+            report.add(Severity::Error, name, format!("build option '{}' is not in the catalog", option));
+        }
+        if unit.buildtime <= 0.0 {
+            report.add(Severity::Error, name, format!("buildtime is {} but has to be positive", unit.buildtime));
+        }
+        // Build progress is measured in metal, so a unit without metal cost is never completed.
+        if unit.m_build_cost <= 0.0 {
+            report.add(Severity::Error, name, format!("metalcost is {} but has to be positive", unit.m_build_cost));
+        }
+        if unit.e_build_cost < 0.0 {
+            report.add(Severity::Error, name, format!("energycost is negative ({})", unit.e_build_cost));
+        }
+        let values = [
+            ("metalmake", unit.m_per_second),
+            ("metalstorage", unit.m_storage),
+            ("energystorage", unit.e_storage),
+            ("windgenerator", unit.wind_e_per_second),
+            ("tidalgenerator", unit.tidal_e_multiplier),
+        ];
+        for (field, value) in values {
+            if value < 0.0 {
+                report.add(Severity::Warning, name, format!("{} is negative ({})", field, value));
+            }
+        }
+        if !unit.build_options.is_empty() && unit.buildpower <= 0.0 {
+            report.add(Severity::Warning, name, "has build options but no workertime".to_string());
+        }
+    }
+    report.sort();
+    report
+}
+
+
+// Checks the raw values of a definition that the loader converts, so that they cannot be told apart later.
+fn lint_definition(report: &mut LintReport, name: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    let lua = Lua::new();
+    let (_, defs) = eval_definition(&lua, &fs::read_to_string(path)?)?;
+    let upkeep = get_float_or(&defs, "energyupkeep", 0.0)?;
+    if upkeep < 0.0 && !defs.contains_key("energymake") {
+        report.add(Severity::Warning, name,
+            format!("negative energyupkeep ({}) without energymake is counted as energy production", upkeep));
+    }
+    Ok(())
+}
+
+
+// Loads every definition in the directory and checks the definitions and the resulting catalog.
+// Unlike loading the catalog, files that fail to load and units that are defined twice are reported
+// instead of being an error or replacing each other.
+pub fn lint_catalog_from_path(catalog_path: &Path) -> Result<LintReport, Box<dyn Error>> {
+    let mut report = LintReport::default();
+    let mut catalog = HashMap::new();
+    let mut definitions: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for path in definition_paths(catalog_path)? {
+        let (name, unit) = match load_catalog_entry(&path) {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.add(Severity::Error, &path.display().to_string(), e.to_string());
+                continue;
+            }
+        };
+        if let Err(e) = lint_definition(&mut report, &name, &path) {
+            report.add(Severity::Error, &path.display().to_string(), e.to_string());
+        }
+        definitions.entry(name.clone()).or_default().push(path);
+        catalog.insert(name, unit);
+    }
+    for (name, paths) in definitions.iter().filter(|(_, paths)| paths.len() > 1) {
+        let paths: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+        report.add(Severity::Error, name, format!("defined in {} files: {}", paths.len(), paths.join(", ")));
+    }
+    report.issues.extend(lint_catalog(&catalog).issues);
+    report.sort();
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_catalog() {
+        let mut catalog = HashMap::new();
+        let mut com = Unit::new_unconstructed(1000.0, 10000.0, 50000.0);
+        com.buildpower = 300.0;
+        com.build_options.insert("mex".to_string());
+        com.build_options.insert("lab".to_string());
+        catalog.insert("commander".to_string(), com);
+        let mut mex = Unit::new_unconstructed(50.0, 500.0, 0.0);
+        mex.m_storage = -50.0;
+        catalog.insert("mex".to_string(), mex);
+        let mut drone = Unit::new_unconstructed(0.0, -10.0, 100.0);
+        drone.build_options.insert("mex".to_string());
+        catalog.insert("drone".to_string(), drone);
+
+        let report = lint_catalog(&catalog);
+        let issues: Vec<(Severity, &str, &str)> = report.issues.iter()
+            .map(|issue| (issue.severity, issue.unit.as_str(), issue.message.as_str()))
+            .collect();
+        assert_eq!(issues, vec![
+            (Severity::Error, "commander", "build option 'lab' is not in the catalog"),
+            (Severity::Error, "drone", "energycost is negative (-10)"),
+            (Severity::Error, "drone", "metalcost is 0 but has to be positive"),
+            (Severity::Error, "mex", "buildtime is 0 but has to be positive"),
+            (Severity::Warning, "drone", "has build options but no workertime"),
+            (Severity::Warning, "mex", "metalstorage is negative (-50)"),
+        ]);
+        assert!(report.has_errors());
+        assert!(report.to_string().ends_with("4 error(s), 2 warning(s)"));
+    }
+}
@@ -1,13 +1,22 @@
-use std::collections::HashSet;
-use std::{collections::HashMap, error::Error, fs, path::Path};
+use std::collections::{HashSet, VecDeque};
+use std::{collections::HashMap, error::Error, fs, path::{Path, PathBuf}};
 
 use mlua::prelude::*;
 use mlua::Value;
 
-use crate::unit::Unit;
+use crate::feature::Feature;
+use crate::unit::{DEFAULT_BUILD_DISTANCE, Unit};
 
 
-fn get_string_or(map: &HashMap<String, Value>, key: &str, default: &str) -> Result<String, Box<dyn Error>> {
+// Keys and values of a definition table
+pub(crate) type DefinitionTable = HashMap<String, Value>;
+
+
+// Simulation frames per second of the engine.
+const GAME_SPEED: f32 = 30.0;
+
+
+pub(crate) fn get_string_or(map: &HashMap<String, Value>, key: &str, default: &str) -> Result<String, Box<dyn Error>> {
     let errmsg = format!("Attempted to parse invalid string for {}.", key);
     Ok(match map.get(key) {
         Some(v) => v.as_string().ok_or(errmsg)?.to_string_lossy(),
@@ -15,7 +24,7 @@ fn get_string_or(map: &HashMap<String, Value>, key: &str, default: &str) -> Resu
     })
 }
 
-fn get_float_or(map: &HashMap<String, Value>, key: &str, default: f32) -> Result<f32, Box<dyn Error>> {
+pub(crate) fn get_float_or(map: &HashMap<String, Value>, key: &str, default: f32) -> Result<f32, Box<dyn Error>> {
     let errmsg = format!("Attempted to parse invalid float for {}.", key);
     Ok(match map.get(key) {
         Some(v) => match v {
@@ -27,6 +36,19 @@ fn get_float_or(map: &HashMap<String, Value>, key: &str, default: f32) -> Result
     })
 }
 
+fn get_bool_or(map: &HashMap<String, Value>, key: &str, default: bool) -> Result<bool, Box<dyn Error>> {
+    let errmsg = format!("Attempted to parse invalid bool for {}.", key);
+    Ok(match map.get(key) {
+        Some(v) => match v {
+            LuaValue::Boolean(b) => *b,
+            LuaValue::Integer(i) => *i != 0,
+            LuaValue::Number(n)  => *n != 0.0,
+            _ => return Err(errmsg.into())
+        }
+        None => default,
+    })
+}
+
 fn get_float(map: &HashMap<String, Value>, key: &str) -> Result<f32, Box<dyn Error>> {
     let errmsg = format!("Attempted to parse invalid float for {}.", key);
     Ok(match map.get(key) {
@@ -50,13 +72,71 @@ pub fn load_definition_from_path(definition_path: &Path) -> Result<Unit, Box<dyn
 }
 
 
+// Loads all unit definitions in the directory and its subdirectories.
+// Units are keyed by the name of their definition table, or by the file name if the table is unnamed.
+pub fn load_catalog_from_path(catalog_path: &Path) -> Result<HashMap<String, Unit>, Box<dyn Error>> {
+    let mut catalog = HashMap::new();
+    for path in definition_paths(catalog_path)? {
+        let (key, unit) = load_catalog_entry(&path)?;
+        catalog.insert(key, unit);
+    }
+    Ok(catalog)
+}
+
+
+// Paths of all definition files in the directory and its subdirectories, sorted so that later files
+// consistently replace earlier ones with the same key.
+pub(crate) fn definition_paths(catalog_path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    let mut dirs = vec![catalog_path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "lua") {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+
+// Loads a definition file of a catalog together with the key of the unit.
+pub(crate) fn load_catalog_entry(path: &Path) -> Result<(String, Unit), Box<dyn Error>> {
+    let definition_str = fs::read_to_string(path)?;
+    let (def_name, mut unit) = parse_named_definition(&definition_str)
+        .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+    let file_stem = path.file_stem().unwrap().display().to_string();
+    if unit.name == "Unknown" {
+        unit.name = file_stem.clone();
+    }
+    Ok((def_name.unwrap_or(file_stem), unit))
+}
+
+
 pub fn parse_definition(definition: &str) -> Result<Unit, Box<dyn Error>> {
-    let lua = Lua::new();
+    Ok(parse_named_definition(definition)?.1)
+}
+
 
-    let mut defs: HashMap<String, Value> = lua.load(definition).eval()?;
+// Evaluates a definition to its table of keys and values, and the name of the table if it has one.
+pub(crate) fn eval_definition(lua: &Lua, definition: &str) -> Result<(Option<String>, DefinitionTable), Box<dyn Error>> {
+    let defs: HashMap<String, Value> = lua.load(definition).eval()?;
     if defs.len() == 1 {
-        defs = HashMap::<String, Value>::from_lua(defs.into_values().next().unwrap(), &lua)?;
+        let (name, lua_map) = defs.into_iter().next().unwrap();
+        return Ok((Some(name), HashMap::<String, Value>::from_lua(lua_map, lua)?))
     }
+    Ok((None, defs))
+}
+
+
+// Also returns the name of the definition table, if the definition has one.
+fn parse_named_definition(definition: &str) -> Result<(Option<String>, Unit), Box<dyn Error>> {
+    let lua = Lua::new();
+    let (def_name, mut defs) = eval_definition(&lua, definition)?;
     
     // Parse energy production and use
     let mut e_per_sec = get_float_or(&defs, "energymake", 0.0)?;
@@ -65,28 +145,96 @@ pub fn parse_definition(definition: &str) -> Result<Unit, Box<dyn Error>> {
         e_per_sec -= e_cost;
         e_cost = 0.0
     }
+    // Geothermal buildings either set the engine flag or mark themselves in the customparams.
+    let customparams = match defs.remove("customparams") {
+        Some(lua_map) => HashMap::<String, Value>::from_lua(lua_map, &lua)?,
+        None => HashMap::new(),
+    };
+    let needs_geo = get_bool_or(&defs, "needgeo", false)? || get_bool_or(&customparams, "geothermal", false)?;
+    // Older definitions give the speed in elmos per frame.
+    let speed = match defs.contains_key("speed") {
+        true => get_float(&defs, "speed")?,
+        false => get_float_or(&defs, "maxvelocity", 0.0)? * GAME_SPEED,
+    };
     // Parse build options
     let build_options = match defs.remove("buildoptions") {
         Some(lua_map) => HashMap::<i32, String>::from_lua(lua_map, &lua)?.into_values().collect(),
         None => HashSet::new(),
     };
 
-    Ok(Unit {
+    // The corpse refers to one of the features defined by the unit itself.
+    let corpse = match defs.get("corpse") {
+        Some(_) => {
+            let corpse_name = get_string_or(&defs, "corpse", "")?.to_lowercase();
+            let featuredefs = match defs.remove("featuredefs") {
+                Some(lua_map) => HashMap::<String, Value>::from_lua(lua_map, &lua)?,
+                None => HashMap::new(),
+            };
+            match featuredefs.into_iter().find(|(name, _)| name.to_lowercase() == corpse_name) {
+                Some((_, def)) => Some(parse_feature(&corpse_name, &HashMap::<String, Value>::from_lua(def, &lua)?)?),
+                None => None,
+            }
+        }
+        None => None,
+    };
+    let buildpower = get_float_or(&defs, "workertime", 0.0)?;
+
+    Ok((def_name, Unit {
         name: get_string_or(&defs, "name", "Unknown")?,
+        def_name: String::new(),
         alive: false,
         metal: 0.0,
         energy: 0.0,
-        buildpower: get_float_or(&defs, "workertime", 0.0)?,
+        buildpower,
         build_target: None,
         build_options,
+        reclaim_speed: get_float_or(&defs, "reclaimspeed", buildpower)?,
+        reclaim_target: None,
+        commands: VecDeque::new(),
+        position: None,
+        speed,
+        build_distance: get_float_or(&defs, "builddistance", 0.0)?.max(DEFAULT_BUILD_DISTANCE),
         buildtime: get_float(&defs, "buildtime")?,
         m_build_cost: get_float(&defs, "metalcost")?,
         e_build_cost: get_float(&defs, "energycost")?,
         e_cost_per_second: e_cost,
         e_per_second: e_per_sec,
         wind_e_per_second: get_float_or(&defs, "windgenerator", 0.0)?,
+        tidal_e_multiplier: get_float_or(&defs, "tidalgenerator", 0.0)?,
         e_storage: get_float_or(&defs, "energystorage", 0.0)?,
         m_per_second: get_float_or(&defs, "metalmake", 0.0)?,
         m_storage: get_float_or(&defs, "metalstorage", 0.0)?,
-    })
-}
\ No newline at end of file
+        corpse,
+        needs_geo,
+        geo_vent: None,
+    }))
+}
+
+
+pub fn load_feature_definitions_from_path(definition_path: &Path) -> Result<HashMap<String, Feature>, Box<dyn Error>> {
+    let definition_str = fs::read_to_string(definition_path)?;
+    parse_feature_definitions(&definition_str)
+}
+
+
+// Parses a feature definition file, which maps feature names to their definitions.
+pub fn parse_feature_definitions(definitions: &str) -> Result<HashMap<String, Feature>, Box<dyn Error>> {
+    let lua = Lua::new();
+
+    let defs: HashMap<String, Value> = lua.load(definitions).eval()?;
+    let mut features = HashMap::new();
+    for (name, def) in defs {
+        let def = HashMap::<String, Value>::from_lua(def, &lua)?;
+        features.insert(name.clone(), parse_feature(&name, &def)?);
+    }
+    Ok(features)
+}
+
+
+fn parse_feature(name: &str, def: &HashMap<String, Value>) -> Result<Feature, Box<dyn Error>> {
+    let metal = get_float_or(def, "metal", 0.0)?;
+    let energy = get_float_or(def, "energy", 0.0)?;
+    let mut feature = Feature::new(metal, energy, get_float_or(def, "reclaimtime", Feature::default_reclaim_time(metal, energy))?);
+    feature.name = name.to_string();
+    Ok(feature)
+}
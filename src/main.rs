@@ -0,0 +1,604 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use rebar::activity::UtilizationReport;
+use rebar::catalog_diff::{compare_timings, diff_catalogs};
+use rebar::charts::{write_charts, Resource, Run};
+use rebar::efficiency::{analyze_producers, break_even_wind, wind_curve, EfficiencyConfig, EfficiencyTable};
+use rebar::forecast::{forecast, ForecastConfig};
+use rebar::evolution::{evolve, EvolutionConfig};
+use rebar::gantt::Timeline;
+use rebar::game_state::GameState;
+use rebar::lint::lint_catalog_from_path;
+use rebar::loader::{load_catalog_from_path, load_definition_from_path};
+use rebar::monte_carlo::{run_monte_carlo, Metric, MonteCarloConfig};
+use rebar::optimizer::{optimize, Objective, SearchConfig};
+use rebar::planner::{plan, Target};
+use rebar::recommender::{recommend, Impact, RecommendConfig};
+use rebar::recorder::Recorder;
+use rebar::scenario::load_scenario_from_path;
+use rebar::snapshot::{load_snapshot, save_snapshot};
+use rebar::tech_tree::TechTree;
+use rebar::unit::Unit;
+
+
+const USAGE: &str = "Usage:
+    rebar simulate <scenario> [--csv <file>] [--json <file>] [--gantt <file.svg>] [--charts <dir>] [--interval <seconds>]
+        [--snapshot <file>]
+    rebar resume <snapshot> --duration <seconds> [--snapshot <file>]
+    rebar forecast <snapshot> [--horizon <seconds>] [--afford <unit,unit,...>]
+    rebar recommend <snapshot> [--builder <unit index>] [--impact metal|energy|income] [--horizon <seconds>]
+    rebar inspect <unitdef.lua>
+    rebar inspect <unit> --catalog <unitdefs dir>
+    rebar compare <scenario> <scenario> [--charts <dir>] [--interval <seconds>]
+    rebar optimize <scenario> (--metal-income <seconds> | --energy-income <seconds> | --reach <units>)
+        [--steps <n>] [--beam <n>] [--units <unit,unit,...>]
+    rebar evolve <scenario> (--metal-income <seconds> | --energy-income <seconds> | --reach <units>)
+        [--steps <n>] [--population <n>] [--generations <n>] [--seed <n>] [--units <unit,unit,...>]
+    rebar monte-carlo <scenario> --metrics <metric,metric,...> [--runs <n>] [--seed <n>] [--max-delay <seconds>]
+        [--wind-interval <seconds>]
+        metrics: finish:<unit>, complete, metal-income:<seconds>, energy-income:<seconds>
+    rebar plan <scenario> [--reach <units>] [--min-metal-income <m/s>] [--min-energy-income <e/s>]
+        [--steps <n>] [--beam <n>] [--units <unit,unit,...>]
+    rebar tech-tree <unitdefs dir> [--from <unit,unit,...>] [--unit <unit>] [--dot <file>]
+    rebar payback <scenario> [--build-power <bp>] [--energy-per-metal <e>] [--winds <wind,wind,...>]
+    rebar lint <unitdefs dir>
+    rebar diff <old unitdefs dir> <new unitdefs dir> [--scenarios <scenario,scenario,...>]";
+
+
+fn print_economy(state: &GameState) {
+    println!("Time:    {:.1}s", state.time);
+    println!("Metal:   {:.1} / {:.1} (+{:.2}/s)", state.metal, state.metal_storage(), state.metal_production());
+    println!("Energy:  {:.1} / {:.1} (+{:.2}/s)", state.energy, state.energy_storage(), state.energy_production());
+    println!("Wasted:  {:.1} metal, {:.1} energy", state.metal_wasted, state.energy_wasted);
+    println!("Units:   {}", state.units.iter().filter(|unit| unit.alive).count());
+}
+
+
+fn print_stalls(state: &GameState) {
+    let stalls = &state.stalls;
+    println!("Stalled: {:.1}s energy, {:.1}s metal", stalls.energy_stall_time, stalls.metal_stall_time);
+    println!("Build power lost to stalls: {:.0} (build power x seconds)", stalls.build_power_lost);
+    for (unit_idx, time) in stalls.most_starved().into_iter().take(5) {
+        println!("    {:>7.1}s  #{} {}", time, unit_idx, state.units[unit_idx].def_name);
+    }
+}
+
+
+fn print_unit(unit: &Unit) {
+    let mut build_options: Vec<&String> = unit.build_options.iter().collect();
+    build_options.sort();
+    println!("Name:           {}", unit.name);
+    println!("Metal cost:     {}", unit.m_build_cost);
+    println!("Energy cost:    {}", unit.e_build_cost);
+    println!("Buildtime:      {}", unit.buildtime);
+    println!("Build power:    {}", unit.buildpower);
+    println!("Reclaim speed:  {}", unit.reclaim_speed);
+    println!("Speed:          {}", unit.speed);
+    println!("Build distance: {}", unit.build_distance);
+    println!("Energy upkeep:  {}/s", unit.e_cost_per_second);
+    println!("Energy make:    {}/s", unit.e_per_second);
+    println!("Wind:           {}/s", unit.wind_e_per_second);
+    println!("Tidal:          x{}", unit.tidal_e_multiplier);
+    println!("Metal make:     {}/s", unit.m_per_second);
+    println!("Energy storage: {}", unit.e_storage);
+    println!("Metal storage:  {}", unit.m_storage);
+    println!("Needs geo:      {}", unit.needs_geo);
+    if let Some(corpse) = &unit.corpse {
+        println!("Corpse:         {} ({} metal, {} energy)", corpse.name, corpse.metal, corpse.energy);
+    }
+    println!("Build options:  {}", build_options.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", "));
+}
+
+
+// Parses trailing `--name value` pairs.
+fn parse_options<'a>(args: &[&'a str]) -> Result<HashMap<&'a str, &'a str>, Box<dyn Error>> {
+    if !args.len().is_multiple_of(2) {
+        return Err(USAGE.into())
+    }
+    let mut options = HashMap::new();
+    for pair in args.chunks(2) {
+        let name = pair[0].strip_prefix("--").ok_or(USAGE)?;
+        options.insert(name, pair[1]);
+    }
+    Ok(options)
+}
+
+
+// The recorder would never get past a sample with an interval that is not positive.
+fn get_interval(options: &HashMap<&str, &str>) -> Result<f32, Box<dyn Error>> {
+    match options.get("interval") {
+        Some(interval) => match interval.parse::<f32>() {
+            Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+            _ => Err(format!("Invalid interval '{}'.", interval).into()),
+        },
+        None => Ok(1.0),
+    }
+}
+
+
+fn simulate(scenario_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let scenario = load_scenario_from_path(scenario_path)?;
+    let mut recorder = Recorder::new(get_interval(options)?)?;
+    let mut timeline = Timeline::new();
+    let (state, report) = scenario.run_recorded(&mut [&mut recorder, &mut timeline])?;
+    println!("Scenario {}", scenario.name);
+    println!("{}", report);
+    println!();
+    print_economy(&state);
+    println!();
+    print_stalls(&state);
+    println!();
+    println!("{}", UtilizationReport::from_state(&state));
+    println!();
+    print!("{}", timeline.to_text(72));
+
+    if let Some(path) = options.get("csv") {
+        recorder.write_csv(Path::new(path))?;
+    }
+    if let Some(path) = options.get("json") {
+        recorder.write_json(Path::new(path))?;
+    }
+    if let Some(path) = options.get("gantt") {
+        timeline.write_svg(Path::new(path))?;
+    }
+    if let Some(dir) = options.get("charts") {
+        write_charts(Path::new(dir), &[Run { name: &scenario.name, samples: &recorder.samples }])?;
+    }
+    if let Some(path) = options.get("snapshot") {
+        save_snapshot(&state, Path::new(path))?;
+    }
+    Ok(())
+}
+
+
+// The duration has to be given. Infinite durations would never end.
+fn get_duration(options: &HashMap<&str, &str>) -> Result<f32, Box<dyn Error>> {
+    let duration = options.get("duration").ok_or(USAGE)?;
+    match duration.parse::<f32>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        _ => Err(format!("Invalid duration '{}'.", duration).into()),
+    }
+}
+
+
+// Continues the simulation from a saved state. Queued commands are carried out, but no build order is run.
+fn resume(snapshot_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let mut state = load_snapshot(snapshot_path)?;
+    let end_time = state.time + get_duration(options)?;
+    while state.time + 1e-4 < end_time {
+        state.simulate(0.1);
+    }
+    print_economy(&state);
+    if let Some(path) = options.get("snapshot") {
+        save_snapshot(&state, Path::new(path))?;
+    }
+    Ok(())
+}
+
+
+// Predicts the economy of a saved game without changing the snapshot.
+fn forecast_snapshot(snapshot_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let state = load_snapshot(snapshot_path)?;
+    let default_config = ForecastConfig::default();
+    let config = ForecastConfig {
+        horizon: parse_number(options, "horizon", default_config.horizon)?,
+        ..default_config
+    };
+    let forecast = forecast(&state, None, &config)?;
+    let last = forecast.last();
+    println!("At {:.1}s:", last.time);
+    println!("Metal:   {:.1} / {:.1} (+{:.2}/s)", last.metal, last.metal_storage, last.metal_income);
+    println!("Energy:  {:.1} / {:.1} (+{:.2}/s)", last.energy, last.energy_storage, last.energy_income);
+    for (name, resource) in [("Metal", Resource::Metal), ("Energy", Resource::Energy)] {
+        match forecast.overflow_time(resource) {
+            Some(time) => println!("{} storage overflows at {:.1}s", name, time),
+            None => println!("{} storage does not overflow", name),
+        }
+    }
+    for window in &forecast.stalls {
+        println!("Stalling {:?} from {:.1}s to {:.1}s", window.resource, window.start, window.end);
+    }
+    for unit in options.get("afford").map_or(Vec::new(), |units| units.split(',').map(str::trim).collect()) {
+        let def = state.unit_catalog.get(unit).ok_or(format!("'{}' is not a known unit.", unit))?;
+        match forecast.afford_time(def) {
+            Some(time) => println!("{} is affordable at {:.1}s", unit, time),
+            None => println!("{} is not affordable within {:.0}s", unit, config.horizon),
+        }
+    }
+    Ok(())
+}
+
+
+// Ranks what a builder of a saved game could build next. Uses the first builder if none is given.
+fn recommend_snapshot(snapshot_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let state = load_snapshot(snapshot_path)?;
+    let builder = match options.get("builder") {
+        Some(_) => parse_number(options, "builder", 0)?,
+        None => state.units.iter().position(|unit| unit.alive && !unit.build_options.is_empty()).ok_or("No unit can build.")?,
+    };
+    let default_config = RecommendConfig::default();
+    let impact = match options.get("impact").copied() {
+        Some("metal") => Impact::MetalIncome,
+        Some("energy") => Impact::EnergyIncome,
+        Some("income") | None => Impact::Income,
+        Some(impact) => return Err(format!("Invalid impact '{}'.", impact).into()),
+    };
+    let config = RecommendConfig {
+        impact,
+        forecast: ForecastConfig {
+            horizon: parse_number(options, "horizon", default_config.forecast.horizon)?,
+            ..default_config.forecast
+        },
+        ..default_config
+    };
+    println!("Builder #{} {}", builder, state.units.get(builder).map_or("", |unit| unit.def_name.as_str()));
+    println!("{}", recommend(&state, builder, &config)?);
+    Ok(())
+}
+
+
+fn inspect(unit: &str, catalog_path: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let unit = match catalog_path {
+        Some(catalog_path) => load_catalog_from_path(&catalog_path)?
+            .remove(unit)
+            .ok_or(format!("'{}' is not a known unit.", unit))?,
+        None => load_definition_from_path(Path::new(unit))?,
+    };
+    print_unit(&unit);
+    Ok(())
+}
+
+
+// Shows how the units of the catalog can be reached from the starting units.
+fn tech_tree(catalog_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let tree = TechTree::from_catalog(&load_catalog_from_path(catalog_path)?);
+    let starts: Vec<&str> = options.get("from").map_or(Vec::new(), |from| from.split(',').map(str::trim).collect());
+    if let Some(unit) = options.get("unit") {
+        if !tree.contains(unit) {
+            return Err(format!("'{}' is not a known unit.", unit).into())
+        }
+        println!("Built by: {}", tree.builders_of(unit).join(", "));
+        for start in &starts {
+            match tree.shortest_chain(start, unit) {
+                Some(chain) => println!("From {}: {}", start, chain.join(" -> ")),
+                None => println!("From {}: unreachable", start),
+            }
+        }
+    } else if !starts.is_empty() {
+        println!("Unreachable: {}", tree.unreachable_from(&starts).join(", "));
+    }
+    if let Some(path) = options.get("dot") {
+        std::fs::write(path, tree.to_dot())?;
+    }
+    Ok(())
+}
+
+
+// Fails if the catalog has errors, so that it can be used in scripts.
+fn lint(catalog_path: &Path) -> Result<(), Box<dyn Error>> {
+    let report = lint_catalog_from_path(catalog_path)?;
+    println!("{}", report);
+    if report.has_errors() {
+        return Err(format!("{} has errors.", catalog_path.display()).into())
+    }
+    Ok(())
+}
+
+
+// Shows the economy changes between two versions of the catalog, and how they shift the timings of scenarios.
+fn diff(old_path: &Path, new_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let old = load_catalog_from_path(old_path)?;
+    let new = load_catalog_from_path(new_path)?;
+    println!("{}", diff_catalogs(&old, &new));
+    for scenario_path in options.get("scenarios").map_or(Vec::new(), |paths| paths.split(',').map(str::trim).collect()) {
+        let scenario = load_scenario_from_path(Path::new(scenario_path))?;
+        match compare_timings(&scenario, &old, &new) {
+            Ok(shift) => println!("\n{}", shift),
+            Err(e) => println!("\n{}", e),
+        }
+    }
+    Ok(())
+}
+
+
+fn compare(scenario_paths: [&Path; 2], options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let mut runs = Vec::new();
+    let mut recorders = Vec::new();
+    for path in scenario_paths {
+        let scenario = load_scenario_from_path(path)?;
+        let mut recorder = Recorder::new(get_interval(options)?)?;
+        let (state, report) = scenario.run_recorded(&mut [&mut recorder])?;
+        runs.push((scenario.name, state, report));
+        recorders.push(recorder);
+    }
+    if let Some(dir) = options.get("charts") {
+        let chart_runs: Vec<Run> = runs.iter().zip(&recorders)
+            .map(|((name, _, _), recorder)| Run { name, samples: &recorder.samples })
+            .collect();
+        write_charts(Path::new(dir), &chart_runs)?;
+    }
+
+    let format_time = |time: Option<f32>| time.map_or("-".to_string(), |time| format!("{:.1}s", time));
+    let (name_a, state_a, report_a) = &runs[0];
+    let (name_b, state_b, report_b) = &runs[1];
+    println!("{:<24} {:>12} {:>12}", "", name_a, name_b);
+    println!("{:<24} {:>12} {:>12}", "Build order finished", format_time(report_a.finish_time()), format_time(report_b.finish_time()));
+    println!("{:<24} {:>12.1} {:>12.1}", "Simulated time", state_a.time, state_b.time);
+    println!("{:<24} {:>12.2} {:>12.2}", "Metal income", state_a.metal_production(), state_b.metal_production());
+    println!("{:<24} {:>12.2} {:>12.2}", "Energy income", state_a.energy_production(), state_b.energy_production());
+    println!("{:<24} {:>12.1} {:>12.1}", "Stored metal", state_a.metal, state_b.metal);
+    println!("{:<24} {:>12.1} {:>12.1}", "Stored energy", state_a.energy, state_b.energy);
+    println!("{:<24} {:>12.1} {:>12.1}", "Wasted metal", state_a.metal_wasted, state_b.metal_wasted);
+    println!("{:<24} {:>12.1} {:>12.1}", "Wasted energy", state_a.energy_wasted, state_b.energy_wasted);
+    println!("{:<24} {:>12.1} {:>12.1}", "Energy stall time", state_a.stalls.energy_stall_time, state_b.stalls.energy_stall_time);
+    println!("{:<24} {:>12.1} {:>12.1}", "Metal stall time", state_a.stalls.metal_stall_time, state_b.stalls.metal_stall_time);
+    println!("{:<24} {:>11.0}% {:>11.0}%", "Build power utilization",
+        100.0 * UtilizationReport::from_state(state_a).utilization(), 100.0 * UtilizationReport::from_state(state_b).utilization());
+    println!("{:<24} {:>12} {:>12}", "Units", state_a.units.iter().filter(|u| u.alive).count(), state_b.units.iter().filter(|u| u.alive).count());
+    Ok(())
+}
+
+
+fn parse_number<T: std::str::FromStr>(options: &HashMap<&str, &str>, name: &str, default: T) -> Result<T, Box<dyn Error>> {
+    match options.get(name) {
+        Some(value) => Ok(value.parse().map_err(|_| format!("Invalid value '{}' for --{}.", value, name))?),
+        None => Ok(default),
+    }
+}
+
+
+fn parse_objective(options: &HashMap<&str, &str>) -> Result<Objective, Box<dyn Error>> {
+    match (options.get("metal-income"), options.get("energy-income"), options.get("reach")) {
+        (Some(_), None, None) => Ok(Objective::MetalIncome(parse_number(options, "metal-income", 0.0)?)),
+        (None, Some(_), None) => Ok(Objective::EnergyIncome(parse_number(options, "energy-income", 0.0)?)),
+        (None, None, Some(units)) => Objective::parse_reach(units),
+        _ => Err(USAGE.into()),
+    }
+}
+
+
+fn parse_allowed_units(options: &HashMap<&str, &str>) -> Option<HashSet<String>> {
+    options.get("units").map(|units| units.split(',').map(|unit| unit.trim().to_string()).collect())
+}
+
+
+// Searches for the build order that best achieves the objective, starting from the scenario's units.
+fn optimize_scenario(scenario_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let scenario = load_scenario_from_path(scenario_path)?;
+    let objective = parse_objective(options)?;
+    let default_config = SearchConfig::default();
+    let config = SearchConfig {
+        allowed_units: parse_allowed_units(options),
+        max_steps: parse_number(options, "steps", default_config.max_steps)?,
+        beam_width: parse_number(options, "beam", default_config.beam_width)?,
+        time_step: scenario.time_step,
+        max_time: scenario.duration,
+    };
+    let best = optimize(&scenario.create_state()?, &objective, &config)?;
+    println!("Best build order: {}", best.build_order);
+    println!("Score:            {:.2}", best.score);
+    Ok(())
+}
+
+
+// Finds the fastest way to reach the target from the scenario's starting units.
+fn plan_scenario(scenario_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let scenario = load_scenario_from_path(scenario_path)?;
+    let target = Target {
+        units: Target::parse_units(options.get("reach").unwrap_or(&""))?,
+        metal_income: parse_number(options, "min-metal-income", 0.0)?,
+        energy_income: parse_number(options, "min-energy-income", 0.0)?,
+        ..Target::default()
+    };
+    let default_config = SearchConfig::default();
+    let config = SearchConfig {
+        allowed_units: parse_allowed_units(options),
+        max_steps: parse_number(options, "steps", default_config.max_steps)?,
+        beam_width: parse_number(options, "beam", default_config.beam_width)?,
+        time_step: scenario.time_step,
+        max_time: scenario.duration,
+    };
+    let plan = plan(&scenario.create_state()?, &target, &config)?;
+    println!("Build order: {}", plan.build_order);
+    match plan.time {
+        Some(time) => println!("Target reached after {:.1}s", time),
+        None => println!("Target not reached within {:.0}s", scenario.duration),
+    }
+    println!("{}", plan.report);
+    Ok(())
+}
+
+
+// Compares the producers of the catalog under the conditions of the scenario.
+// Wind generators are also shown at other wind strengths, by default the minimum, average and maximum of the map.
+fn payback(scenario_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let scenario = load_scenario_from_path(scenario_path)?;
+    let state = scenario.create_state()?;
+    let default_config = EfficiencyConfig::default();
+    let config = EfficiencyConfig {
+        energy_per_metal: parse_number(options, "energy-per-metal", default_config.energy_per_metal)?,
+        build_power: parse_number(options, "build-power", default_config.build_power)?,
+    };
+    let winds = match options.get("winds") {
+        Some(winds) => winds.split(',').map(|wind| wind.trim().parse().map_err(|_| format!("Invalid wind '{}'.", wind)))
+            .collect::<Result<Vec<f32>, _>>()?,
+        None => vec![state.map.min_wind, state.map.average_wind(), state.map.max_wind],
+    };
+    let producers = analyze_producers(&state, &config);
+    println!("At wind {:.1}:", state.wind_strength);
+    println!("{}", EfficiencyTable(&producers));
+    // Geothermal is limited by the vents and builders are not built for their income.
+    let energy_producers: Vec<&str> = producers.iter()
+        .filter(|row| row.energy_income > 0.0 && !row.needs_geo)
+        .filter(|row| state.unit_catalog[&row.unit].wind_e_per_second == 0.0 && state.unit_catalog[&row.unit].build_options.is_empty())
+        .map(|row| row.unit.as_str())
+        .collect();
+    for wind_unit in producers.iter().filter(|row| state.unit_catalog[&row.unit].wind_e_per_second > 0.0) {
+        let curve = wind_curve(&state, &wind_unit.unit, &config, &winds).unwrap();
+        println!("\n{}:", wind_unit.unit);
+        for (wind, row) in winds.iter().zip(curve) {
+            match row.payback_time {
+                Some(time) => println!("    wind {:5.1}: pays back after {:.1}s", wind, time),
+                None => println!("    wind {:5.1}: never pays back", wind),
+            }
+        }
+        for other in &energy_producers {
+            match break_even_wind(&state, &wind_unit.unit, other, &config) {
+                Some(wind) => println!("    better than {} from wind {:.1}", other, wind),
+                None => println!("    never better than {}", other),
+            }
+        }
+    }
+    Ok(())
+}
+
+
+// Runs the scenario many times with random wind and reaction times and shows the spread of the outcomes.
+fn monte_carlo(scenario_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let scenario = load_scenario_from_path(scenario_path)?;
+    let metrics = options.get("metrics").ok_or(USAGE)?.split(',').map(Metric::parse).collect::<Result<Vec<_>, _>>()?;
+    let default_config = MonteCarloConfig::default();
+    let config = MonteCarloConfig {
+        runs: parse_number(options, "runs", default_config.runs)?,
+        seed: parse_number(options, "seed", default_config.seed)?,
+        max_command_delay: parse_number(options, "max-delay", default_config.max_command_delay)?,
+        wind_interval: match options.get("wind-interval") {
+            Some(_) => Some(parse_number(options, "wind-interval", 0.0)?),
+            None => None,
+        },
+        ..default_config
+    };
+    println!("{}", run_monte_carlo(&scenario, &metrics, &config)?);
+    Ok(())
+}
+
+
+// Evolves build orders for the objective, starting from the scenario's build order.
+fn evolve_scenario(scenario_path: &Path, options: &HashMap<&str, &str>) -> Result<(), Box<dyn Error>> {
+    let scenario = load_scenario_from_path(scenario_path)?;
+    let objective = parse_objective(options)?;
+    let default_config = EvolutionConfig::default();
+    let config = EvolutionConfig {
+        allowed_units: parse_allowed_units(options),
+        population: parse_number(options, "population", default_config.population)?,
+        generations: parse_number(options, "generations", default_config.generations)?,
+        max_steps: parse_number(options, "steps", default_config.max_steps)?,
+        seed: parse_number(options, "seed", default_config.seed)?,
+        duration: scenario.duration,
+        time_step: scenario.time_step,
+        ..default_config
+    };
+    let result = evolve(&scenario.create_state()?, &objective, std::slice::from_ref(&scenario.build_order), &config)?;
+    println!("Best build order: {}", result.best.build_order);
+    println!("Fitness:          {:.2}", result.best.fitness);
+    Ok(())
+}
+
+
+fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["simulate", scenario, options @ ..] => simulate(Path::new(scenario), &parse_options(options)?),
+        ["resume", snapshot, options @ ..] => resume(Path::new(snapshot), &parse_options(options)?),
+        ["forecast", snapshot, options @ ..] => forecast_snapshot(Path::new(snapshot), &parse_options(options)?),
+        ["recommend", snapshot, options @ ..] => recommend_snapshot(Path::new(snapshot), &parse_options(options)?),
+        ["optimize", scenario, options @ ..] => optimize_scenario(Path::new(scenario), &parse_options(options)?),
+        ["evolve", scenario, options @ ..] => evolve_scenario(Path::new(scenario), &parse_options(options)?),
+        ["monte-carlo", scenario, options @ ..] => monte_carlo(Path::new(scenario), &parse_options(options)?),
+        ["plan", scenario, options @ ..] => plan_scenario(Path::new(scenario), &parse_options(options)?),
+        ["tech-tree", catalog, options @ ..] => tech_tree(Path::new(catalog), &parse_options(options)?),
+        ["payback", scenario, options @ ..] => payback(Path::new(scenario), &parse_options(options)?),
+        ["diff", old, new, options @ ..] => diff(Path::new(old), Path::new(new), &parse_options(options)?),
+        ["lint", catalog] => lint(Path::new(catalog)),
+        ["inspect", unit] => inspect(unit, None),
+        ["inspect", unit, "--catalog", catalog] => inspect(unit, Some(PathBuf::from(catalog))),
+        ["compare", scenario_a, scenario_b, options @ ..] => compare([Path::new(scenario_a), Path::new(scenario_b)], &parse_options(options)?),
+        _ => Err(USAGE.into()),
+    }
+}
+
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse_options(&["--csv", "out.csv", "--interval", "5"]).unwrap();
+        assert_eq!(options, HashMap::from([("csv", "out.csv"), ("interval", "5")]));
+        assert!(parse_options(&[]).unwrap().is_empty());
+        // Every option needs a value, and names start with two dashes.
+        assert!(parse_options(&["--csv"]).is_err());
+        assert!(parse_options(&["csv", "out.csv"]).is_err());
+        assert!(run(&["simulate".to_string()]).is_err());
+    }
+
+
+    #[test]
+    fn test_parse_number() {
+        let options = HashMap::from([("steps", "12"), ("beam", "-1")]);
+        assert_eq!(parse_number(&options, "steps", 10).unwrap(), 12);
+        assert_eq!(parse_number(&options, "runs", 100).unwrap(), 100);
+        let err = parse_number::<usize>(&options, "beam", 8).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid value '-1' for --beam.");
+    }
+
+
+    #[test]
+    fn test_get_interval() {
+        assert_eq!(get_interval(&HashMap::new()).unwrap(), 1.0);
+        assert_eq!(get_interval(&HashMap::from([("interval", "2.5")])).unwrap(), 2.5);
+        for interval in ["0", "-1", "NaN", "inf", "soon"] {
+            assert!(get_interval(&HashMap::from([("interval", interval)])).is_err());
+        }
+    }
+
+
+    #[test]
+    fn test_get_duration() {
+        assert_eq!(get_duration(&HashMap::from([("duration", "30")])).unwrap(), 30.0);
+        assert!(get_duration(&HashMap::new()).is_err());
+        for duration in ["0", "-5", "inf", "NaN", "long"] {
+            assert!(get_duration(&HashMap::from([("duration", duration)])).is_err());
+        }
+    }
+
+
+    #[test]
+    fn test_parse_objective() {
+        let objective = parse_objective(&HashMap::from([("metal-income", "300")])).unwrap();
+        assert_eq!(objective, Objective::MetalIncome(300.0));
+        let objective = parse_objective(&HashMap::from([("energy-income", "120")])).unwrap();
+        assert_eq!(objective, Objective::EnergyIncome(120.0));
+        let objective = parse_objective(&HashMap::from([("reach", "armlab, armck x2")])).unwrap();
+        assert_eq!(objective, Objective::Reach(HashMap::from([("armlab".to_string(), 1), ("armck".to_string(), 2)])));
+
+        // Exactly one objective has to be given.
+        assert!(parse_objective(&HashMap::new()).is_err());
+        assert!(parse_objective(&HashMap::from([("metal-income", "300"), ("reach", "armlab")])).is_err());
+        assert!(parse_objective(&HashMap::from([("metal-income", "lots")])).is_err());
+    }
+
+
+    #[test]
+    fn test_parse_allowed_units() {
+        assert_eq!(parse_allowed_units(&HashMap::new()), None);
+        let units = parse_allowed_units(&HashMap::from([("units", "armmex, armsolar")])).unwrap();
+        assert_eq!(units, HashSet::from(["armmex".to_string(), "armsolar".to_string()]));
+    }
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+// Position on the map in elmos. The height axis is not needed for the economy.
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Position {
+    pub x: f32,
+    pub z: f32,
+}
+
+
+impl Position {
+    pub fn new(x: f32, z: f32) -> Position {
+        Position { x, z }
+    }
+
+
+    pub fn distance(&self, other: &Position) -> f32 {
+        (self.x - other.x).hypot(self.z - other.z)
+    }
+
+
+    // Step from this position towards the target, without overshooting it.
+    pub fn towards(&self, target: &Position, step: f32) -> Position {
+        let distance = self.distance(target);
+        if distance <= step {
+            return *target;
+        }
+        let fraction = step / distance;
+        Position::new(self.x + fraction * (target.x - self.x), self.z + fraction * (target.z - self.z))
+    }
+}
+
+
+// A cluster of metal on the metal map that one extractor can be placed on.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MetalSpot {
+    pub position: Position,
+    // Summed metal density of all metal map pixels belonging to the spot.
+    pub metal: f32,
+}
+
+
+// Static information about the map that the economy depends on.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct MapData {
+    pub name: String,
+    pub min_wind: f32,
+    pub max_wind: f32,
+    pub tidal_strength: f32,
+    pub max_metal: f32,
+    pub metal_spots: Vec<MetalSpot>,
+    // Geothermal vents. Units that need geothermal can only be built on one of these.
+    pub geo_vents: Vec<Position>,
+}
+
+
+impl Default for MapData {
+    // Defaults taken from the Spring engine for maps without a mapinfo.lua
+    fn default() -> Self {
+        MapData {
+            name: "Unknown".to_string(),
+            min_wind: 5.0,
+            max_wind: 25.0,
+            tidal_strength: 0.0,
+            max_metal: 0.02,
+            metal_spots: Vec::new(),
+            geo_vents: Vec::new(),
+        }
+    }
+}
+
+
+impl MapData {
+    // The engine draws the wind strength between the map's minimum and maximum.
+    // For a deterministic simulation we use the middle of that range.
+    pub fn average_wind(&self) -> f32 {
+        0.5 * (self.min_wind + self.max_wind)
+    }
+}
@@ -0,0 +1,192 @@
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+use mlua::prelude::*;
+use mlua::Value;
+
+use crate::loader::get_float_or;
+use crate::map::{MapData, MetalSpot, Position};
+
+
+const SMF_MAGIC: &[u8; 16] = b"spring map file\0";
+const SMF_HEADER_SIZE: usize = 80;
+// Every metal map pixel covers two by two map squares.
+const SMF_METAL_MAP_SCALE: usize = 2;
+
+
+// Lua tables in mapinfo files are not consistent about capitalization, e.g. `maxMetal` vs `maxmetal`.
+fn lowercase_table(table: LuaTable) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let mut map = HashMap::new();
+    for pair in table.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        if let Some(key) = key.as_string() {
+            map.insert(key.to_string_lossy().to_lowercase(), value);
+        }
+    }
+    Ok(map)
+}
+
+
+fn read_i32(data: &[u8], offset: usize) -> Result<i32, Box<dyn Error>> {
+    let bytes = data.get(offset..offset + 4).ok_or("SMF file is truncated.")?;
+    Ok(i32::from_le_bytes(bytes.try_into()?))
+}
+
+
+// Loads the economy relevant parts of an extracted map archive.
+// The directory is expected to contain `mapinfo.lua` and the `.smf` file, either directly or in `maps/`.
+pub fn load_map_from_path(map_path: &Path) -> Result<MapData, Box<dyn Error>> {
+    let mapinfo_str = fs::read_to_string(map_path.join("mapinfo.lua"))?;
+    let mut map = parse_mapinfo(&mapinfo_str)?;
+
+    let smf_path = [map_path.join("maps"), map_path.to_path_buf()].iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("smf")))
+        .ok_or(format!("No .smf file found in {}.", map_path.display()))?;
+    map.metal_spots = parse_smf_metal_spots(&fs::read(smf_path)?, map.max_metal)?;
+    Ok(map)
+}
+
+
+pub fn parse_mapinfo(mapinfo: &str) -> Result<MapData, Box<dyn Error>> {
+    let lua = Lua::new();
+
+    // Many mapinfo files try to include optional config files through the engine's virtual file system.
+    let vfs = lua.create_table()?;
+    vfs.set("FileExists", lua.create_function(|_, _: LuaMultiValue| Ok(false))?)?;
+    vfs.set("Include", lua.create_function(|lua, _: LuaMultiValue| lua.create_table())?)?;
+    vfs.set("DirList", lua.create_function(|lua, _: LuaMultiValue| lua.create_table())?)?;
+    lua.globals().set("VFS", vfs)?;
+    lua.globals().set("Spring", lua.create_table()?)?;
+
+    let defs = lowercase_table(lua.load(mapinfo).eval()?)?;
+    let atmosphere = match defs.get("atmosphere") {
+        Some(Value::Table(table)) => lowercase_table(table.clone())?,
+        _ => HashMap::new(),
+    };
+
+    let default = MapData::default();
+    let name = match defs.get("name") {
+        Some(v) => v.as_string().ok_or("Attempted to parse invalid string for name.")?.to_string_lossy(),
+        None => default.name,
+    };
+    Ok(MapData {
+        name,
+        min_wind: get_float_or(&atmosphere, "minwind", default.min_wind)?,
+        max_wind: get_float_or(&atmosphere, "maxwind", default.max_wind)?,
+        tidal_strength: get_float_or(&defs, "tidalstrength", default.tidal_strength)?,
+        max_metal: get_float_or(&defs, "maxmetal", default.max_metal)?,
+        metal_spots: Vec::new(),
+        geo_vents: Vec::new(),
+    })
+}
+
+
+// Reads the metal map layer of an SMF file and groups connected metal pixels into spots.
+pub fn parse_smf_metal_spots(smf: &[u8], max_metal: f32) -> Result<Vec<MetalSpot>, Box<dyn Error>> {
+    if smf.len() < SMF_HEADER_SIZE || &smf[0..16] != SMF_MAGIC {
+        return Err("Not a valid SMF file.".into())
+    }
+    let map_x = read_i32(smf, 24)?;
+    let map_z = read_i32(smf, 28)?;
+    let square_size = read_i32(smf, 32)?;
+    let metal_map_ptr = read_i32(smf, 68)?;
+    if map_x <= 0 || map_z <= 0 || square_size <= 0 || metal_map_ptr < 0 {
+        return Err("SMF header contains invalid dimensions.".into())
+    }
+
+    let width = map_x as usize / SMF_METAL_MAP_SCALE;
+    let height = map_z as usize / SMF_METAL_MAP_SCALE;
+    let start = metal_map_ptr as usize;
+    let metal_map = smf.get(start..start + width * height).ok_or("SMF metal map is truncated.")?;
+    let pixel_size = (square_size as usize * SMF_METAL_MAP_SCALE) as f32;
+
+    // Flood fill every unvisited metal pixel to find the spots.
+    let mut visited = vec![false; metal_map.len()];
+    let mut spots = Vec::new();
+    for start_idx in 0..metal_map.len() {
+        if visited[start_idx] || metal_map[start_idx] == 0 {
+            continue;
+        }
+        let mut stack = vec![start_idx];
+        visited[start_idx] = true;
+        let (mut metal, mut x_sum, mut z_sum) = (0.0, 0.0, 0.0);
+        while let Some(idx) = stack.pop() {
+            let (x, z) = (idx % width, idx / width);
+            let value = metal_map[idx] as f32 / 255.0 * max_metal;
+            metal += value;
+            x_sum += value * (x as f32 + 0.5) * pixel_size;
+            z_sum += value * (z as f32 + 0.5) * pixel_size;
+
+            for (nx, nz) in [(x.wrapping_sub(1), z), (x + 1, z), (x, z.wrapping_sub(1)), (x, z + 1)] {
+                if nx < width && nz < height {
+                    let n_idx = nx + nz * width;
+                    if !visited[n_idx] && metal_map[n_idx] != 0 {
+                        visited[n_idx] = true;
+                        stack.push(n_idx);
+                    }
+                }
+            }
+        }
+        spots.push(MetalSpot {
+            position: Position::new(x_sum / metal, z_sum / metal),
+            metal,
+        });
+    }
+    Ok(spots)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    // Creates an SMF file that only contains a header and the metal map.
+    fn smf_with_metal_map(map_x: i32, map_z: i32, metal_map: &[u8]) -> Vec<u8> {
+        let mut smf = Vec::new();
+        smf.extend_from_slice(SMF_MAGIC);
+        for value in [1, 0, map_x, map_z, 8, 8, 32] {
+            smf.extend_from_slice(&i32::to_le_bytes(value));
+        }
+        smf.extend_from_slice(&f32::to_le_bytes(0.0));
+        smf.extend_from_slice(&f32::to_le_bytes(100.0));
+        for value in [0, 0, 0, 0, SMF_HEADER_SIZE as i32, 0, 0] {
+            smf.extend_from_slice(&i32::to_le_bytes(value));
+        }
+        smf.extend_from_slice(metal_map);
+        smf
+    }
+
+
+    #[test]
+    fn test_metal_spots() {
+        #[rustfmt::skip]
+        let metal_map = [
+            255, 255, 0, 0,
+            255, 255, 0, 0,
+            0,   0,   0, 0,
+            0,   0,   0, 51,
+        ];
+        let smf = smf_with_metal_map(8, 8, &metal_map);
+        let spots = parse_smf_metal_spots(&smf, 2.0).unwrap();
+
+        assert_eq!(spots.len(), 2);
+        assert_abs_diff_eq!(spots[0].metal, 8.0);
+        assert_abs_diff_eq!(spots[0].position.x, 16.0, epsilon = 1e-3);
+        assert_abs_diff_eq!(spots[0].position.z, 16.0, epsilon = 1e-3);
+        assert_abs_diff_eq!(spots[1].metal, 0.4);
+        assert_abs_diff_eq!(spots[1].position.x, 56.0, epsilon = 1e-3);
+        assert_abs_diff_eq!(spots[1].position.z, 56.0, epsilon = 1e-3);
+    }
+
+
+    #[test]
+    fn test_invalid_smf() {
+        assert!(parse_smf_metal_spots(b"not a map", 2.0).is_err());
+        let smf = smf_with_metal_map(8, 8, &[255; 4]);
+        assert!(parse_smf_metal_spots(&smf, 2.0).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use crate::unit::Unit;
+
+// Lobby options of BAR that change the unit definitions, given as multipliers.
+#[derive(PartialEq, Clone, Debug)]
+pub struct ModOptions {
+    pub resource_income: f32,
+    pub energy_production: f32,
+    pub build_power: f32,
+    pub build_distance: f32,
+    pub metal_cost: f32,
+    pub energy_cost: f32,
+    pub buildtime_cost: f32,
+}
+
+
+impl Default for ModOptions {
+    // Defaults taken from BAR
+    fn default() -> Self {
+        DEFAULT_MOD_OPTIONS.clone()
+    }
+}
+
+
+pub const DEFAULT_MOD_OPTIONS: ModOptions = ModOptions {
+    resource_income: 1.0,
+    energy_production: 1.0,
+    build_power: 1.0,
+    build_distance: 1.0,
+    metal_cost: 1.0,
+    energy_cost: 1.0,
+    buildtime_cost: 1.0,
+};
+
+
+impl ModOptions {
+    // Apply the multipliers to a unit template.
+    pub fn apply(&self, unit: &mut Unit) {
+        let e_production = self.resource_income * self.energy_production;
+        unit.e_per_second *= e_production;
+        unit.wind_e_per_second *= e_production;
+        unit.tidal_e_multiplier *= e_production;
+        unit.m_per_second *= self.resource_income;
+        unit.buildpower *= self.build_power;
+        unit.reclaim_speed *= self.build_power;
+        unit.build_distance *= self.build_distance;
+        unit.m_build_cost *= self.metal_cost;
+        unit.e_build_cost *= self.energy_cost;
+        unit.buildtime *= self.buildtime_cost;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_apply() {
+        let mut wind = Unit::new_unconstructed(40.0, 175.0, 1600.0);
+        wind.wind_e_per_second = 25.0;
+        let options = ModOptions { resource_income: 2.0, metal_cost: 0.5, ..ModOptions::default() };
+        options.apply(&mut wind);
+
+        assert_abs_diff_eq!(wind.wind_e_per_second, 50.0);
+        assert_abs_diff_eq!(wind.m_build_cost, 20.0);
+        assert_abs_diff_eq!(wind.e_build_cost, 175.0);
+        assert_abs_diff_eq!(wind.buildtime, 1600.0);
+    }
+}
@@ -0,0 +1,276 @@
+use std::error::Error;
+use std::fmt;
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+
+use crate::build_order::{validate_time_step, BuildOrderRunner};
+use crate::evolution::Simulation;
+use crate::game_state::GameState;
+use crate::recorder::{Observer, Recorder};
+use crate::scenario::Scenario;
+
+// An outcome of a run. Times are in seconds since the start of the game.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Metric {
+    // Time at which the build order finished the first unit of the kind
+    FinishTime(String),
+    // Time at which the build order is complete
+    CompletionTime,
+    // Income at the given time
+    MetalIncome(f32),
+    EnergyIncome(f32),
+}
+
+
+impl Metric {
+    // Parses "finish:<unit>", "complete", "metal-income:<seconds>" and "energy-income:<seconds>".
+    pub fn parse(text: &str) -> Result<Metric, Box<dyn Error>> {
+        let parse_time = |time: &str| time.trim().parse::<f32>().map_err(|_| format!("Invalid time '{}'.", time));
+        match text.trim().split_once(':') {
+            Some(("finish", unit)) => Ok(Metric::FinishTime(unit.trim().to_string())),
+            Some(("metal-income", time)) => Ok(Metric::MetalIncome(parse_time(time)?)),
+            Some(("energy-income", time)) => Ok(Metric::EnergyIncome(parse_time(time)?)),
+            None if text.trim() == "complete" => Ok(Metric::CompletionTime),
+            _ => Err(format!("Invalid metric '{}'.", text).into()),
+        }
+    }
+
+
+    // None if the run did not get there, e.g. because the unit was not finished within the duration.
+    pub fn measure(&self, simulation: &Simulation) -> Option<f32> {
+        let sample_at = |time: f32| simulation.samples.iter().find(|sample| sample.time + 1e-3 >= time);
+        match self {
+            Metric::FinishTime(unit) => simulation.report.steps.iter()
+                .filter(|step| step.unit == *unit)
+                .filter_map(|step| step.finished)
+                .min_by(f32::total_cmp),
+            Metric::CompletionTime => simulation.report.finish_time(),
+            Metric::MetalIncome(time) => sample_at(*time).map(|sample| sample.metal_income),
+            Metric::EnergyIncome(time) => sample_at(*time).map(|sample| sample.energy_income),
+        }
+    }
+}
+
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::FinishTime(unit) => write!(f, "{} finished", unit),
+            Metric::CompletionTime => write!(f, "build order complete"),
+            Metric::MetalIncome(time) => write!(f, "metal income at {}s", time),
+            Metric::EnergyIncome(time) => write!(f, "energy income at {}s", time),
+        }
+    }
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct MonteCarloConfig {
+    pub runs: usize,
+    // Each run uses its own seed derived from this one, so the results do not depend on the number of threads.
+    pub seed: u64,
+    // Wind is drawn uniformly between the minimum and maximum wind of the map at the start of every run,
+    // and again every interval if one is given.
+    pub wind_interval: Option<f32>,
+    // Before every step of the build order, the builder waits for a random time up to this delay.
+    pub max_command_delay: f32,
+    pub sample_interval: f32,
+    // Number of threads that run the scenario. Zero uses all available cores.
+    pub threads: usize,
+}
+
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        MonteCarloConfig {
+            runs: 100,
+            seed: 0,
+            wind_interval: None,
+            max_command_delay: 0.0,
+            sample_interval: 1.0,
+            threads: 0,
+        }
+    }
+}
+
+
+// Values of a metric over all runs that reached it.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Distribution {
+    pub metric: Metric,
+    // Sorted
+    pub values: Vec<f32>,
+    // Runs that did not reach the metric
+    pub missing: usize,
+}
+
+
+impl Distribution {
+    // Percentile between 0 and 100, interpolated between the closest values.
+    pub fn percentile(&self, percentile: f32) -> Option<f32> {
+        let last = self.values.len().checked_sub(1)?;
+        let rank = percentile.clamp(0.0, 100.0) / 100.0 * last as f32;
+        let lower = rank.floor() as usize;
+        let upper = rank.ceil() as usize;
+        Some(self.values[lower] + (rank - lower as f32) * (self.values[upper] - self.values[lower]))
+    }
+
+
+    pub fn mean(&self) -> Option<f32> {
+        if self.values.is_empty() {
+            return None
+        }
+        let mut sum = 0.0;
+        for value in &self.values {
+            sum += value;
+        }
+        Some(sum / self.values.len() as f32)
+    }
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct MonteCarloResult {
+    pub runs: usize,
+    pub distributions: Vec<Distribution>,
+}
+
+
+impl fmt::Display for MonteCarloResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_value = |value: Option<f32>| match value {
+            Some(value) => format!("{:8.2}", value),
+            None => "       -".to_string(),
+        };
+        writeln!(f, "{} runs", self.runs)?;
+        write!(f, "{:<30} {:>8}  {:>8}  {:>8}  {:>8}  {:>8}", "metric", "p10", "p50", "p90", "mean", "missing")?;
+        for distribution in &self.distributions {
+            write!(f, "\n{:<30} {}  {}  {}  {}  {:8}", distribution.metric.to_string(),
+                format_value(distribution.percentile(10.0)), format_value(distribution.percentile(50.0)),
+                format_value(distribution.percentile(90.0)), format_value(distribution.mean()), distribution.missing)?;
+        }
+        Ok(())
+    }
+}
+
+
+fn draw_wind(state: &mut GameState, rng: &mut StdRng) {
+    let (min_wind, max_wind) = (state.map.min_wind, state.map.max_wind);
+    state.wind_strength = if max_wind > min_wind { rng.random_range(min_wind..=max_wind) } else { min_wind };
+}
+
+
+fn validate(scenario: &Scenario, config: &MonteCarloConfig) -> Result<(), Box<dyn Error>> {
+    validate_time_step(scenario.time_step)?;
+    if !scenario.duration.is_finite() {
+        return Err(format!("Invalid duration {}.", scenario.duration).into())
+    }
+    if !config.max_command_delay.is_finite() || config.max_command_delay < 0.0 {
+        return Err(format!("Invalid command delay {}.", config.max_command_delay).into())
+    }
+    if !config.sample_interval.is_finite() || config.sample_interval <= 0.0 {
+        return Err(format!("Invalid sample interval {}.", config.sample_interval).into())
+    }
+    if let Some(interval) = config.wind_interval && (!interval.is_finite() || interval <= 0.0) {
+        return Err(format!("Invalid wind interval {}.", interval).into())
+    }
+    Ok(())
+}
+
+
+// Runs the scenario once with the random inputs of the given run.
+pub fn run_once(scenario: &Scenario, state: &GameState, config: &MonteCarloConfig, run: usize) -> Result<Simulation, Box<dyn Error>> {
+    validate(scenario, config)?;
+    let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(run as u64));
+    let mut state = state.clone();
+    draw_wind(&mut state, &mut rng);
+    let mut runner = BuildOrderRunner::new(scenario.build_order.clone(), &state)?;
+    if config.max_command_delay > 0.0 {
+        runner.set_delays(scenario.build_order.queues.iter().map(|queue| {
+            queue.steps.iter().map(|_| rng.random_range(0.0..=config.max_command_delay)).collect()
+        }).collect());
+    }
+    let mut recorder = Recorder::new(config.sample_interval)?;
+    recorder.record(&state);
+    let mut next_wind = config.wind_interval.map(|interval| state.time + interval);
+    while state.time < scenario.duration {
+        if let (Some(time), Some(interval)) = (next_wind, config.wind_interval) && state.time + 1e-4 >= time {
+            draw_wind(&mut state, &mut rng);
+            next_wind = Some(time + interval);
+        }
+        runner.step(&mut state, scenario.time_step);
+        recorder.record(&state);
+    }
+    let report = runner.report(&state);
+    Ok(Simulation { state, report, samples: recorder.samples })
+}
+
+
+// Runs the scenario many times with random wind and command delays and collects the metrics of all runs.
+// The wind of the scenario is replaced by the random draws.
+pub fn run_monte_carlo(scenario: &Scenario, metrics: &[Metric], config: &MonteCarloConfig) -> Result<MonteCarloResult, Box<dyn Error>> {
+    // Fail early on settings and build orders that cannot be executed at all, before the threads start.
+    validate(scenario, config)?;
+    let state = scenario.create_state()?;
+    BuildOrderRunner::new(scenario.build_order.clone(), &state)?;
+    let threads = match config.threads {
+        0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    };
+    let runs: Vec<usize> = (0..config.runs).collect();
+    let chunk_size = runs.len().div_ceil(threads).max(1);
+    // Errors are not sent between threads, so they are passed on as text.
+    let measurements: Vec<Vec<Option<f32>>> = thread::scope(|scope| {
+        let handles: Vec<_> = runs.chunks(chunk_size).map(|chunk| {
+            let state = &state;
+            scope.spawn(move || chunk.iter().map(|run| {
+                let simulation = run_once(scenario, state, config, *run).map_err(|e| e.to_string())?;
+                Ok(metrics.iter().map(|metric| metric.measure(&simulation)).collect())
+            }).collect::<Result<Vec<Vec<Option<f32>>>, String>>())
+        }).collect();
+        handles.into_iter()
+            .map(|handle| handle.join().map_err(|_| "A Monte Carlo run panicked.".to_string())?)
+            .collect::<Result<Vec<_>, String>>()
+    })?.into_iter().flatten().collect();
+
+    let distributions = metrics.iter().enumerate().map(|(metric_idx, metric)| {
+        let mut values: Vec<f32> = measurements.iter().filter_map(|measured| measured[metric_idx]).collect();
+        values.sort_by(f32::total_cmp);
+        Distribution { metric: metric.clone(), missing: config.runs - values.len(), values }
+    }).collect();
+    Ok(MonteCarloResult { runs: config.runs, distributions })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_metric() {
+        assert_eq!(Metric::parse("finish:armlab").unwrap(), Metric::FinishTime("armlab".to_string()));
+        assert_eq!(Metric::parse("metal-income: 300").unwrap(), Metric::MetalIncome(300.0));
+        assert_eq!(Metric::parse("complete").unwrap(), Metric::CompletionTime);
+        assert!(Metric::parse("energy-income:soon").is_err());
+        assert!(Metric::parse("armlab").is_err());
+    }
+
+
+    #[test]
+    fn test_percentile() {
+        let distribution = Distribution {
+            metric: Metric::CompletionTime,
+            values: vec![10.0, 20.0, 30.0, 40.0, 50.0],
+            missing: 1,
+        };
+        assert_abs_diff_eq!(distribution.percentile(50.0).unwrap(), 30.0);
+        assert_abs_diff_eq!(distribution.percentile(10.0).unwrap(), 14.0);
+        assert_abs_diff_eq!(distribution.percentile(100.0).unwrap(), 50.0);
+        assert_abs_diff_eq!(distribution.mean().unwrap(), 30.0);
+        let empty = Distribution { values: Vec::new(), ..distribution };
+        assert!(empty.percentile(50.0).is_none());
+    }
+}
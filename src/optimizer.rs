@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use crate::build_order::{parse_step, validate_time_step, BuildOrder, BuildOrderRunner, BuildQueue};
+use crate::game_state::GameState;
+
+// What the optimizer tries to achieve. Higher scores are better.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Objective {
+    // Maximize the metal income at the given time.
+    MetalIncome(f32),
+    // Maximize the energy income at the given time.
+    EnergyIncome(f32),
+    // Have at least this many of each unit as early as possible.
+    Reach(HashMap<String, usize>),
+}
+
+
+impl Objective {
+    // Parses a list of units like "armlab, armck x2" for the `Reach` objective.
+    pub fn parse_reach(text: &str) -> Result<Objective, Box<dyn Error>> {
+        let mut targets = HashMap::new();
+        for step in text.split(',').map(str::trim).filter(|step| !step.is_empty()) {
+            let (unit, count) = parse_step(step)?;
+            if count == 0 {
+                return Err(format!("Invalid count in '{}'.", step).into())
+            }
+            *targets.entry(unit.to_string()).or_default() += count;
+        }
+        if targets.is_empty() {
+            return Err("No units to reach.".into())
+        }
+        Ok(Objective::Reach(targets))
+    }
+
+
+    // Number of target units that do not exist yet.
+    fn missing(targets: &HashMap<String, usize>, state: &GameState) -> usize {
+        targets.iter().map(|(name, count)| {
+            let existing = state.units.iter().filter(|unit| unit.alive && unit.def_name == *name).count();
+            count.saturating_sub(existing)
+        }).sum()
+    }
+
+
+    // Score of the `Reach` objective. Reaching the targets scores minus the time it took.
+    // Orders that miss targets within the time limit score below all that reach them.
+    pub(crate) fn reach_score(missing: usize, reach_time: f32, time_limit: f32) -> f32 {
+        match missing {
+            0 => -reach_time,
+            _ => -time_limit * (1 + missing) as f32,
+        }
+    }
+
+
+    // Runs the build order on a copy of the state and scores the result.
+    pub fn evaluate(&self, state: &GameState, build_order: &BuildOrder, config: &SearchConfig) -> Result<f32, Box<dyn Error>> {
+        validate_time_step(config.time_step)?;
+        let mut state = state.clone();
+        let mut runner = BuildOrderRunner::new(build_order.clone(), &state)?;
+        match self {
+            Objective::MetalIncome(time) | Objective::EnergyIncome(time) => {
+                while state.time + 1e-4 < *time {
+                    runner.step(&mut state, config.time_step);
+                }
+                Ok(match self {
+                    Objective::MetalIncome(_) => state.metal_production(),
+                    _ => state.energy_production(),
+                })
+            }
+            Objective::Reach(targets) => loop {
+                let missing = Objective::missing(targets, &state);
+                if missing == 0 || state.time >= config.max_time {
+                    return Ok(Objective::reach_score(missing, state.time, config.max_time))
+                }
+                runner.step(&mut state, config.time_step);
+            },
+        }
+    }
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct SearchConfig {
+    // Units that may be added to the build order. Without a list, every unit in the catalog may be built.
+    pub allowed_units: Option<HashSet<String>>,
+    // Maximum number of steps of the build order.
+    pub max_steps: usize,
+    // Number of build orders that are kept after every step.
+    pub beam_width: usize,
+    pub time_step: f32,
+    // Time limit for reaching the targets.
+    pub max_time: f32,
+}
+
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            allowed_units: None,
+            max_steps: 10,
+            beam_width: 8,
+            time_step: 0.5,
+            max_time: 600.0,
+        }
+    }
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Candidate {
+    pub build_order: BuildOrder,
+    pub score: f32,
+}
+
+
+// Beam search for the build order that best achieves the objective.
+pub fn optimize(state: &GameState, objective: &Objective, config: &SearchConfig) -> Result<Candidate, Box<dyn Error>> {
+    beam_search(state, config, |build_order| objective.evaluate(state, build_order, config))
+}
+
+
+// Every round, each kept build order is extended by one step in every possible way,
+// and only the build orders with the best scores are kept for the next round.
+pub(crate) fn beam_search<E>(state: &GameState, config: &SearchConfig, evaluate: E) -> Result<Candidate, Box<dyn Error>>
+    where E: Fn(&BuildOrder) -> Result<f32, Box<dyn Error>> {
+    if config.beam_width < 1 {
+        return Err("The beam width must be at least 1.".into())
+    }
+    validate_time_step(config.time_step)?;
+    if !config.max_time.is_finite() {
+        return Err(format!("Invalid time limit {}.", config.max_time).into())
+    }
+    let empty = BuildOrder::default();
+    let mut best = Candidate { score: evaluate(&empty)?, build_order: empty };
+    let mut beam = vec![best.clone()];
+    for _ in 0..config.max_steps {
+        // Different interleavings of the queues result in the same build order.
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        for candidate in &beam {
+            for build_order in expand(state, &candidate.build_order, config) {
+                if !seen.insert(build_order.to_string()) {
+                    continue;
+                }
+                // Orders that cannot be evaluated are dropped. Steps that cannot be started, e.g. because there are not
+                // enough geothermal vents, fail without stopping the order, so they only lower the score.
+                if let Ok(score) = evaluate(&build_order) {
+                    candidates.push(Candidate { build_order, score });
+                }
+            }
+        }
+        if candidates.is_empty() {
+            break;
+        }
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        candidates.truncate(config.beam_width);
+        if let Some(first) = candidates.first() && first.score > best.score {
+            best = first.clone();
+        }
+        beam = candidates;
+    }
+    Ok(best)
+}
+
+
+// Kinds of builders that exist at the start or are built by the order.
+pub(crate) fn builders(state: &GameState, build_order: &BuildOrder) -> Vec<String> {
+    let mut builders: Vec<String> = Vec::new();
+    let existing = state.units.iter().filter(|unit| unit.alive).map(|unit| unit.def_name.as_str());
+    let built = build_order.queues.iter().flat_map(|queue| queue.steps.iter().map(String::as_str));
+    for name in existing.chain(built) {
+        let can_build = state.unit_catalog.get(name).is_some_and(|unit| !unit.build_options.is_empty());
+        if can_build && !builders.iter().any(|builder| builder == name) {
+            builders.push(name.to_string());
+        }
+    }
+    builders
+}
+
+
+// Sorted units in the catalog that the builder may build.
+pub(crate) fn build_options(state: &GameState, builder: &str, allowed_units: &Option<HashSet<String>>) -> Vec<String> {
+    let Some(builder) = state.unit_catalog.get(builder) else {
+        return Vec::new();
+    };
+    let mut options: Vec<String> = builder.build_options.iter()
+        .filter(|unit| state.unit_catalog.contains_key(*unit))
+        .filter(|unit| allowed_units.as_ref().is_none_or(|allowed| allowed.contains(*unit)))
+        .cloned()
+        .collect();
+    options.sort();
+    options
+}
+
+
+// All build orders that have one more step than the given one.
+fn expand(state: &GameState, build_order: &BuildOrder, config: &SearchConfig) -> Vec<BuildOrder> {
+    let mut build_orders = Vec::new();
+    for builder in builders(state, build_order) {
+        for unit in build_options(state, &builder, &config.allowed_units) {
+            let mut build_order = build_order.clone();
+            match build_order.queues.iter_mut().find(|queue| queue.builder == builder) {
+                Some(queue) => queue.steps.push(unit),
+                None => build_order.queues.push(BuildQueue { builder: builder.clone(), steps: vec![unit] }),
+            }
+            build_orders.push(build_order);
+        }
+    }
+    build_orders
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::tech_1_state;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_metal_income() {
+        let state = tech_1_state();
+        let config = SearchConfig {
+            allowed_units: Some(HashSet::from(["mex".to_string(), "solar".to_string()])),
+            max_steps: 3,
+            ..SearchConfig::default()
+        };
+        let best = optimize(&state, &Objective::MetalIncome(30.0), &config).unwrap();
+        assert_eq!(best.build_order.to_string(), "commander: mex x3");
+        assert_abs_diff_eq!(best.score, 6.0);
+        // The state that was optimized for is not changed.
+        assert_eq!(state.units.len(), 1);
+
+        let objective = Objective::MetalIncome(30.0);
+        assert!(optimize(&state, &objective, &SearchConfig { beam_width: 0, ..config.clone() }).is_err());
+        // The simulation would never get to the time of the objective.
+        assert!(optimize(&state, &objective, &SearchConfig { time_step: 0.0, ..config.clone() }).is_err());
+        assert!(objective.evaluate(&state, &BuildOrder::default(), &SearchConfig { time_step: -1.0, ..config.clone() }).is_err());
+        assert!(optimize(&state, &objective, &SearchConfig { max_time: f32::INFINITY, ..config }).is_err());
+    }
+
+
+    #[test]
+    fn test_reach() {
+        let state = tech_1_state();
+        let objective = Objective::parse_reach("lab, con x2").unwrap();
+        let config = SearchConfig { max_steps: 3, beam_width: 4, ..SearchConfig::default() };
+        let best = optimize(&state, &objective, &config).unwrap();
+        assert_eq!(best.build_order.to_string(), "commander: lab; lab: con x2");
+        // Five seconds for the lab and three seconds for each constructor
+        assert_abs_diff_eq!(best.score, -11.0, epsilon = 0.5);
+
+        assert!(Objective::parse_reach("").is_err());
+        assert!(Objective::parse_reach("lab x0").is_err());
+    }
+}
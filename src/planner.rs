@@ -0,0 +1,214 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use crate::build_order::{parse_step, validate_time_step, BuildOrder, BuildOrderReport, BuildOrderRunner};
+use crate::game_state::GameState;
+use crate::optimizer::{beam_search, SearchConfig};
+use crate::tech_tree::TechTree;
+
+// A condition on the game state that a plan should fulfill as early as possible.
+pub trait Goal {
+    fn is_reached(&self, state: &GameState) -> bool;
+
+
+    // How far the state is from the goal. Used to compare plans that do not reach the goal.
+    fn shortfall(&self, state: &GameState) -> f32 {
+        if self.is_reached(state) { 0.0 } else { 1.0 }
+    }
+
+
+    // Units that have to be built to reach the goal.
+    fn required_units(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+
+impl<F: Fn(&GameState) -> bool> Goal for F {
+    fn is_reached(&self, state: &GameState) -> bool {
+        self(state)
+    }
+}
+
+
+// Units to own and minimum income and storage.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Target {
+    pub units: HashMap<String, usize>,
+    pub metal_income: f32,
+    pub energy_income: f32,
+    pub metal_storage: f32,
+    pub energy_storage: f32,
+}
+
+
+impl Target {
+    // Parses a list of units like "armlab, armck x2".
+    pub fn parse_units(text: &str) -> Result<HashMap<String, usize>, Box<dyn Error>> {
+        let mut units = HashMap::new();
+        for step in text.split(',').map(str::trim).filter(|step| !step.is_empty()) {
+            let (unit, count) = parse_step(step)?;
+            *units.entry(unit.to_string()).or_default() += count;
+        }
+        Ok(units)
+    }
+
+
+    fn missing_units(&self, state: &GameState) -> usize {
+        self.units.iter().map(|(name, count)| {
+            let existing = state.units.iter().filter(|unit| unit.alive && unit.def_name == *name).count();
+            count.saturating_sub(existing)
+        }).sum()
+    }
+}
+
+
+impl Goal for Target {
+    fn is_reached(&self, state: &GameState) -> bool {
+        self.shortfall(state) <= 0.0
+    }
+
+
+    // Number of missing units plus the missing fraction of each resource requirement.
+    fn shortfall(&self, state: &GameState) -> f32 {
+        let fraction_missing = |have: f32, need: f32| if need > 0.0 { (1.0 - have / need).max(0.0) } else { 0.0 };
+        self.missing_units(state) as f32
+            + fraction_missing(state.metal_production(), self.metal_income)
+            + fraction_missing(state.energy_production(), self.energy_income)
+            + fraction_missing(state.metal_storage(), self.metal_storage)
+            + fraction_missing(state.energy_storage(), self.energy_storage)
+    }
+
+
+    fn required_units(&self) -> Vec<String> {
+        self.units.keys().cloned().collect()
+    }
+}
+
+
+// The fastest build order that was found for a goal.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Plan {
+    pub build_order: BuildOrder,
+    // Time at which the goal is reached. None if no plan reaches the goal within the time limit.
+    pub time: Option<f32>,
+    // Start and finish of every step, up to the time the goal is reached.
+    pub report: BuildOrderReport,
+}
+
+
+// Units that can be built, directly or through a chain of builders, by the completed units of the state.
+pub fn reachable_units(state: &GameState) -> HashSet<String> {
+    let starts: Vec<&str> = state.units.iter().filter(|unit| unit.alive).map(|unit| unit.def_name.as_str()).collect();
+    TechTree::from_catalog(&state.unit_catalog).reachable_from(&starts).into_iter().collect()
+}
+
+
+// Runs the build order until the goal is reached or the time limit is hit.
+fn run_until<G: Goal + ?Sized>(state: &GameState, build_order: &BuildOrder, goal: &G, config: &SearchConfig)
+    -> Result<(GameState, BuildOrderReport), Box<dyn Error>> {
+    validate_time_step(config.time_step)?;
+    let mut state = state.clone();
+    let mut runner = BuildOrderRunner::new(build_order.clone(), &state)?;
+    while !goal.is_reached(&state) && state.time < config.max_time {
+        runner.step(&mut state, config.time_step);
+    }
+    let report = runner.report(&state);
+    Ok((state, report))
+}
+
+
+// Searches for the build order that reaches the goal the earliest.
+// The beam search can miss the earliest build order, so the plan is the best one found within the beam width.
+// Only units that the starting units can reach through their build options are considered.
+pub fn plan<G: Goal + ?Sized>(state: &GameState, goal: &G, config: &SearchConfig) -> Result<Plan, Box<dyn Error>> {
+    let reachable = reachable_units(state);
+    for unit in goal.required_units() {
+        if !state.unit_catalog.contains_key(&unit) {
+            return Err(format!("'{}' is not a known unit.", unit).into())
+        }
+        if !reachable.contains(&unit) {
+            return Err(format!("'{}' cannot be built from the starting units.", unit).into())
+        }
+    }
+    let allowed_units = match &config.allowed_units {
+        Some(allowed) => allowed.intersection(&reachable).cloned().collect(),
+        None => reachable,
+    };
+    let config = SearchConfig { allowed_units: Some(allowed_units), ..config.clone() };
+
+    // Reaching the goal scores minus the time it took. Other plans score below that, the further away the better.
+    let best = beam_search(state, &config, |build_order| {
+        let (end_state, _) = run_until(state, build_order, goal, &config)?;
+        Ok(match goal.is_reached(&end_state) {
+            true => -end_state.time,
+            false => -config.max_time * (1.0 + goal.shortfall(&end_state)),
+        })
+    })?;
+
+    let (end_state, report) = run_until(state, &best.build_order, goal, &config)?;
+    Ok(Plan {
+        build_order: best.build_order,
+        time: goal.is_reached(&end_state).then_some(end_state.time),
+        report,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::tech_1_state;
+    use crate::unit::Unit;
+    use approx::assert_abs_diff_eq;
+
+    fn state_with_unreachable_units() -> GameState {
+        let mut state = tech_1_state();
+        // Nothing can build the factory for these.
+        let mut t2_lab = Unit::new_unconstructed(1000.0, 1000.0, 5000.0);
+        t2_lab.build_options.insert("t2_con".to_string());
+        state.register_unit("t2_lab", t2_lab);
+        state.register_unit("t2_con", Unit::new_unconstructed(300.0, 300.0, 900.0));
+        state
+    }
+
+
+    #[test]
+    fn test_reachable_units() {
+        let state = state_with_unreachable_units();
+        let reachable = reachable_units(&state);
+        assert_eq!(reachable, HashSet::from(["commander", "mex", "solar", "lab", "con"].map(String::from)));
+    }
+
+
+    #[test]
+    fn test_plan() {
+        let state = state_with_unreachable_units();
+        let goal = Target {
+            units: Target::parse_units("con").unwrap(),
+            metal_income: 4.0,
+            ..Target::default()
+        };
+        let config = SearchConfig { max_steps: 4, beam_width: 6, ..SearchConfig::default() };
+        let plan = plan(&state, &goal, &config).unwrap();
+        // The lab and the constructor take eight seconds, in which the commander can also build two mexes
+        // after the lab is done.
+        assert_eq!(plan.build_order.to_string(), "commander: lab, mex x2; lab: con");
+        assert_abs_diff_eq!(plan.time.unwrap(), 8.0, epsilon = 0.5);
+        assert_eq!(plan.report.steps.len(), 4);
+        assert!(plan.report.is_complete());
+
+        let unreachable = Target { units: Target::parse_units("t2_con").unwrap(), ..Target::default() };
+        assert!(super::plan(&state, &unreachable, &config).is_err());
+
+        // Any predicate can be used as goal.
+        let income_goal = |state: &GameState| state.metal_production() >= 6.0;
+        let plan = super::plan(&state, &income_goal, &config).unwrap();
+        assert_eq!(plan.build_order.to_string(), "commander: mex x3");
+
+        assert!(super::plan(&state, &income_goal, &SearchConfig { beam_width: 0, ..config.clone() }).is_err());
+        let config = SearchConfig { time_step: 0.0, ..config };
+        assert!(super::plan(&state, &income_goal, &config).is_err());
+        assert!(run_until(&state, &BuildOrder::default(), &income_goal, &config).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::charts::Resource;
+use crate::command::Command;
+use crate::efficiency::{is_producer, Efficiency, EfficiencyConfig};
+use crate::forecast::{forecast_recorded, Forecast, ForecastConfig};
+use crate::game_state::GameState;
+use crate::recorder::Observer;
+
+// What a recommendation should improve by the end of the horizon.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Impact {
+    MetalIncome,
+    EnergyIncome,
+    // Metal income plus energy income converted to metal
+    Income,
+}
+
+
+impl Impact {
+    // Energy income is net of upkeep, like in the payback analysis.
+    pub fn score(&self, state: &GameState, energy_per_metal: f32) -> f32 {
+        let energy_income = state.energy_production() - state.energy_upkeep();
+        match self {
+            Impact::MetalIncome => state.metal_production(),
+            Impact::EnergyIncome => energy_income,
+            Impact::Income => state.metal_production() + energy_income / energy_per_metal,
+        }
+    }
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct RecommendConfig {
+    pub impact: Impact,
+    pub forecast: ForecastConfig,
+    // Conversion rate for `Impact::Income` and the payback times.
+    pub energy_per_metal: f32,
+}
+
+
+impl Default for RecommendConfig {
+    fn default() -> Self {
+        RecommendConfig {
+            impact: Impact::Income,
+            forecast: ForecastConfig::default(),
+            energy_per_metal: EfficiencyConfig::default().energy_per_metal,
+        }
+    }
+}
+
+
+// The predicted outcome of building one of the builder's options next.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Recommendation {
+    pub unit: String,
+    // Impact at the end of the horizon, and how much better that is than building nothing.
+    pub score: f32,
+    pub gain: f32,
+    // When the unit is finished. None if it is not finished within the horizon.
+    pub finish_time: Option<f32>,
+    // Time that metal and energy run short within the horizon.
+    pub stall_time: f32,
+    // Payback time with the builder's build power, for units that produce resources.
+    pub payback_time: Option<f32>,
+    // When each storage starts to overflow within the horizon.
+    pub metal_overflow: Option<f32>,
+    pub energy_overflow: Option<f32>,
+}
+
+
+// Build options of a builder, the best first.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Recommendations {
+    pub builder: usize,
+    pub impact: Impact,
+    // Outcome if the builder does not get another order.
+    pub baseline: f32,
+    pub baseline_stall_time: f32,
+    pub rows: Vec<Recommendation>,
+}
+
+
+impl fmt::Display for Recommendations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_time = |time: Option<f32>| match time {
+            Some(time) => format!("{:7.1}s", time),
+            None => "       -".to_string(),
+        };
+        writeln!(f, "{:?} without a new order: {:.2}, stalling {:.1}s", self.impact, self.baseline, self.baseline_stall_time)?;
+        write!(f, "unit               score      gain  finished   stalled   payback  metal full  energy full")?;
+        for row in &self.rows {
+            write!(f, "\n{:<14} {:9.2} {:+9.2}  {}  {:7.1}s  {}    {}     {}", row.unit, row.score, row.gain,
+                format_time(row.finish_time), row.stall_time, format_time(row.payback_time),
+                format_time(row.metal_overflow), format_time(row.energy_overflow))?;
+        }
+        Ok(())
+    }
+}
+
+
+// Finds the unit that the queued build command creates and notes when it is finished.
+struct BuildWatcher {
+    builder: usize,
+    unit: String,
+    unit_count: usize,
+    unit_idx: Option<usize>,
+    finish_time: Option<f32>,
+}
+
+
+impl Observer for BuildWatcher {
+    fn record(&mut self, state: &GameState) {
+        // The command is the last in the queue, so the unit is created in the step that empties the queue.
+        if self.unit_idx.is_none() && state.units[self.builder].commands.is_empty() {
+            self.unit_idx = (self.unit_count..state.units.len()).rev().find(|idx| state.units[*idx].def_name == self.unit);
+            // The command could not be executed.
+            if self.unit_idx.is_none() {
+                self.unit_idx = Some(usize::MAX);
+            }
+        }
+        if let Some(unit_idx) = self.unit_idx && self.finish_time.is_none()
+            && state.units.get(unit_idx).is_some_and(|unit| unit.alive) {
+            self.finish_time = Some(state.time);
+        }
+        self.unit_count = state.units.len();
+    }
+}
+
+
+// Scores every forecasted state, so that the score of the last one is kept.
+struct ImpactWatcher {
+    impact: Impact,
+    energy_per_metal: f32,
+    score: f32,
+}
+
+
+impl Observer for ImpactWatcher {
+    fn record(&mut self, state: &GameState) {
+        self.score = self.impact.score(state, self.energy_per_metal);
+    }
+}
+
+
+fn stall_time(forecast: &Forecast) -> f32 {
+    forecast.stall_time(Resource::Metal) + forecast.stall_time(Resource::Energy)
+}
+
+
+// Forecasts the economy for every unit the builder can build, queued after its current orders,
+// and ranks the units by their impact at the end of the horizon. The state is not changed.
+pub fn recommend(state: &GameState, builder: usize, config: &RecommendConfig) -> Result<Recommendations, Box<dyn Error>> {
+    let builder_unit = state.units.get(builder).ok_or(format!("Unit {} does not exist.", builder))?;
+    if !builder_unit.alive || builder_unit.build_options.is_empty() {
+        return Err(format!("Unit {} cannot build anything.", builder).into())
+    }
+    let new_watcher = || ImpactWatcher { impact: config.impact, energy_per_metal: config.energy_per_metal, score: 0.0 };
+    let mut baseline_watcher = new_watcher();
+    let baseline = forecast_recorded(state, None, &config.forecast, &mut [&mut baseline_watcher])?;
+    let efficiency_config = EfficiencyConfig { energy_per_metal: config.energy_per_metal, build_power: builder_unit.buildpower };
+
+    let mut options: Vec<&String> = builder_unit.build_options.iter().filter(|unit| state.unit_catalog.contains_key(*unit)).collect();
+    options.sort();
+    let mut rows = Vec::new();
+    for unit in options {
+        let mut state = state.clone();
+        // Options that the builder cannot be ordered to build are left out.
+        if state.queue_command(builder, Command::Build { unit: unit.clone(), position: None }).is_err() {
+            continue;
+        }
+        let mut watcher = BuildWatcher {
+            builder,
+            unit: unit.clone(),
+            unit_count: state.units.len(),
+            unit_idx: None,
+            finish_time: None,
+        };
+        let mut impact_watcher = new_watcher();
+        let forecast = forecast_recorded(&state, None, &config.forecast, &mut [&mut watcher, &mut impact_watcher])?;
+        let score = impact_watcher.score;
+        let def = &state.unit_catalog[unit];
+        rows.push(Recommendation {
+            unit: unit.clone(),
+            score,
+            gain: score - baseline_watcher.score,
+            finish_time: watcher.finish_time,
+            stall_time: stall_time(&forecast),
+            payback_time: match is_producer(def) {
+                true => Efficiency::new(unit, def, &state, state.wind_strength, &efficiency_config).payback_time,
+                false => None,
+            },
+            metal_overflow: forecast.overflow_time(Resource::Metal),
+            energy_overflow: forecast.overflow_time(Resource::Energy),
+        });
+    }
+    // Ties, e.g. between units that are not finished in time, go to the unit that causes fewer stalls.
+    rows.sort_by(|a, b| b.score.total_cmp(&a.score)
+        .then(a.stall_time.total_cmp(&b.stall_time))
+        .then_with(|| a.unit.cmp(&b.unit)));
+    Ok(Recommendations {
+        builder,
+        impact: config.impact,
+        baseline: baseline_watcher.score,
+        baseline_stall_time: stall_time(&baseline),
+        rows,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit::Unit;
+    use crate::world_params::WorldParams;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_recommend() {
+        let mut state = GameState::new(WorldParams::default());
+        state.metal = 200.0;
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 100.0;
+        com.m_per_second = 1.0;
+        com.e_per_second = 20.0;
+        for option in ["mex", "solar", "lab"] {
+            com.build_options.insert(option.to_string());
+        }
+        state.register_unit("commander", com);
+        let mut mex = Unit::new_unconstructed(50.0, 50.0, 100.0);
+        mex.m_per_second = 2.0;
+        mex.e_cost_per_second = 3.0;
+        state.register_unit("mex", mex);
+        let mut solar = Unit::new_unconstructed(150.0, 0.0, 200.0);
+        solar.e_per_second = 20.0;
+        state.register_unit("solar", solar);
+        state.register_unit("lab", Unit::new_unconstructed(600.0, 1200.0, 5000.0));
+        state.add_completed_unit("commander").unwrap();
+
+        let original = state.clone();
+        let config = RecommendConfig {
+            impact: Impact::MetalIncome,
+            forecast: ForecastConfig { horizon: 30.0, time_step: 0.5 },
+            ..RecommendConfig::default()
+        };
+        let recommendations = recommend(&state, 0, &config).unwrap();
+        assert_eq!(state, original);
+        assert_abs_diff_eq!(recommendations.baseline, 1.0);
+
+        let names: Vec<&str> = recommendations.rows.iter().map(|row| row.unit.as_str()).collect();
+        assert_eq!(names, vec!["mex", "solar", "lab"]);
+        let mex = &recommendations.rows[0];
+        assert_abs_diff_eq!(mex.gain, 2.0);
+        assert_abs_diff_eq!(mex.finish_time.unwrap(), 1.0, epsilon = 1e-3);
+        assert!(mex.payback_time.is_some());
+        // The lab is too expensive to be finished in time, and makes the builder stall on metal.
+        let lab = &recommendations.rows[2];
+        assert!(lab.finish_time.is_none());
+        assert!(lab.payback_time.is_none());
+        assert!(lab.stall_time > 0.0);
+
+        let config = RecommendConfig { impact: Impact::EnergyIncome, ..config };
+        let recommendations = recommend(&state, 0, &config).unwrap();
+        assert_eq!(recommendations.rows[0].unit, "solar");
+        assert_abs_diff_eq!(recommendations.rows[0].finish_time.unwrap(), 2.0, epsilon = 1e-3);
+        // The lab does not change the energy income, but the mex costs 3 energy per second of upkeep.
+        assert_eq!(recommendations.rows[1].unit, "lab");
+        assert_abs_diff_eq!(recommendations.rows[1].gain, 0.0);
+        assert_eq!(recommendations.rows[2].unit, "mex");
+        assert_abs_diff_eq!(recommendations.rows[2].gain, -3.0);
+    }
+}
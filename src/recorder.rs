@@ -0,0 +1,211 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::game_state::GameState;
+
+// Economy metrics of the game state at one point in time. Rates are given per second.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct Sample {
+    pub time: f32,
+    pub metal: f32,
+    pub energy: f32,
+    pub metal_storage: f32,
+    pub energy_storage: f32,
+    pub metal_income: f32,
+    pub energy_income: f32,
+    pub metal_pull: f32,
+    pub energy_pull: f32,
+    pub metal_expense: f32,
+    pub energy_expense: f32,
+    pub build_power: f32,
+    pub build_power_used: f32,
+    pub metal_waste: f32,
+    pub energy_waste: f32,
+    // Cumulative waste since the start of the game
+    pub metal_wasted: f32,
+    pub energy_wasted: f32,
+    // Cumulative stalls since the start of the game
+    pub energy_stall_time: f32,
+    pub metal_stall_time: f32,
+    pub build_power_lost: f32,
+}
+
+
+impl Sample {
+    pub fn from_state(state: &GameState) -> Sample {
+        Sample {
+            time: state.time,
+            metal: state.metal,
+            energy: state.energy,
+            metal_storage: state.metal_storage(),
+            energy_storage: state.energy_storage(),
+            metal_income: state.metal_production(),
+            energy_income: state.energy_production(),
+            metal_pull: state.metal_pull(),
+            energy_pull: state.energy_pull(),
+            metal_expense: state.metal_expense(),
+            energy_expense: state.energy_expense(),
+            build_power: state.build_power(),
+            build_power_used: state.build_power_used(),
+            metal_waste: state.metal_waste(),
+            energy_waste: state.energy_waste(),
+            metal_wasted: state.metal_wasted,
+            energy_wasted: state.energy_wasted,
+            energy_stall_time: state.stalls.energy_stall_time,
+            metal_stall_time: state.stalls.metal_stall_time,
+            build_power_lost: state.stalls.build_power_lost,
+        }
+    }
+}
+
+
+const CSV_HEADER: &str = "time,metal,energy,metal_storage,energy_storage,metal_income,energy_income,\
+    metal_pull,energy_pull,metal_expense,energy_expense,build_power,build_power_used,\
+    metal_waste,energy_waste,metal_wasted,energy_wasted,energy_stall_time,metal_stall_time,build_power_lost";
+
+
+// Something that watches the game state while it is being simulated.
+pub trait Observer {
+    // Called once before the simulation starts and after every simulation step.
+    fn record(&mut self, state: &GameState);
+}
+
+
+// Samples the game state at a fixed interval while it is being simulated.
+#[derive(Clone, Debug)]
+pub struct Recorder {
+    pub interval: f32,
+    pub samples: Vec<Sample>,
+    next_sample: f32,
+}
+
+
+impl Recorder {
+    // The interval has to be positive, otherwise recording would never get past the first sample.
+    pub fn new(interval: f32) -> Result<Recorder, Box<dyn Error>> {
+        if !interval.is_finite() || interval <= 0.0 {
+            return Err(format!("Invalid sample interval {}.", interval).into())
+        }
+        Ok(Recorder {
+            interval,
+            samples: Vec::new(),
+            next_sample: 0.0,
+        })
+    }
+
+
+    // Advance the state and record it.
+    pub fn simulate(&mut self, state: &mut GameState, dt: f32) {
+        state.simulate(dt);
+        self.record(state);
+    }
+
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for s in &self.samples {
+            writeln!(csv, "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                s.time, s.metal, s.energy, s.metal_storage, s.energy_storage, s.metal_income, s.energy_income,
+                s.metal_pull, s.energy_pull, s.metal_expense, s.energy_expense, s.build_power, s.build_power_used,
+                s.metal_waste, s.energy_waste, s.metal_wasted, s.energy_wasted,
+                s.energy_stall_time, s.metal_stall_time, s.build_power_lost
+            ).unwrap();
+        }
+        csv
+    }
+
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(&self.samples)?)
+    }
+
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_csv())?;
+        Ok(())
+    }
+
+
+    pub fn write_json(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+
+impl Observer for Recorder {
+    // Takes a sample if the next sampling time has been reached.
+    fn record(&mut self, state: &GameState) {
+        // Small tolerance, so that accumulated time steps do not skip a sample.
+        if state.time + 1e-4 >= self.next_sample {
+            self.samples.push(Sample::from_state(state));
+            while self.next_sample <= state.time + 1e-4 {
+                self.next_sample += self.interval;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit::Unit;
+    use crate::world_params::WorldParams;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_interval() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.e_per_second = 30.0;
+        com.m_per_second = 2.0;
+        state.register_unit("commander", com);
+        state.add_completed_unit("commander").unwrap();
+        state.metal = 0.0;
+        state.energy = 0.0;
+
+        let mut recorder = Recorder::new(1.0).unwrap();
+        recorder.record(&state);
+        for _ in 0..30 {
+            recorder.simulate(&mut state, 0.1);
+        }
+
+        assert_eq!(recorder.samples.len(), 4);
+        assert_abs_diff_eq!(recorder.samples[0].time, 0.0);
+        assert_abs_diff_eq!(recorder.samples[3].time, 3.0, epsilon = 1e-4);
+        assert_abs_diff_eq!(recorder.samples[2].metal, 4.0, epsilon = 1e-4);
+        assert_abs_diff_eq!(recorder.samples[2].energy, 60.0, epsilon = 1e-3);
+        assert_abs_diff_eq!(recorder.samples[2].metal_income, 2.0);
+        assert_abs_diff_eq!(recorder.samples[2].metal_storage, 500.0);
+
+        for interval in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(Recorder::new(interval).is_err());
+        }
+    }
+
+
+    #[test]
+    fn test_export() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut recorder = Recorder::new(1.0).unwrap();
+        recorder.record(&state);
+        recorder.simulate(&mut state, 1.0);
+
+        let csv = recorder.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "0,1000,1000,500,500,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0");
+        assert_eq!(lines[2], "1,500,500,500,500,0,0,0,0,0,0,0,0,500,500,500,500,0,0,0");
+
+        let json: serde_json::Value = serde_json::from_str(&recorder.to_json().unwrap()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(json[1]["metal"], 500.0);
+    }
+}
@@ -0,0 +1,266 @@
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+use mlua::prelude::*;
+use mlua::Value;
+
+use crate::build_order::{validate_time_step, BuildOrder, BuildOrderReport, BuildOrderRunner};
+use crate::feature::Feature;
+use crate::game_state::GameState;
+use crate::loader::{get_float_or, get_string_or, load_catalog_from_path, load_feature_definitions_from_path};
+use crate::map::{MapData, Position};
+use crate::map_loader::load_map_from_path;
+use crate::mod_options::ModOptions;
+use crate::recorder::Observer;
+use crate::unit::Unit;
+use crate::world_params::WorldParams;
+
+
+// A unit that exists at the start of the scenario.
+#[derive(PartialEq, Clone, Debug)]
+pub struct StartingUnit {
+    pub name: String,
+    // Fraction of the unit that is already built. Units with a progress of one are completed.
+    pub progress: f32,
+    pub position: Option<Position>,
+}
+
+
+// A feature that is placed on the map at the start of the scenario.
+#[derive(PartialEq, Clone, Debug)]
+pub struct StartingFeature {
+    pub name: String,
+    pub position: Option<Position>,
+}
+
+
+// Everything needed to set up and run a simulation.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub name: String,
+    pub catalog: HashMap<String, Unit>,
+    pub feature_catalog: HashMap<String, Feature>,
+    pub mod_options: ModOptions,
+    pub world_params: WorldParams,
+    pub map: Option<MapData>,
+    pub geo_vents: Vec<Position>,
+    pub wind_strength: Option<f32>,
+    pub tidal_strength: Option<f32>,
+    pub starting_units: Vec<StartingUnit>,
+    pub starting_features: Vec<StartingFeature>,
+    pub build_order: BuildOrder,
+    pub time_step: f32,
+    pub duration: f32,
+}
+
+
+impl Scenario {
+    // Creates the game state at the start of the scenario.
+    pub fn create_state(&self) -> Result<GameState, Box<dyn Error>> {
+        let mut state = GameState::new(self.world_params.clone());
+        for (name, unit) in &self.catalog {
+            let mut unit = unit.clone();
+            self.mod_options.apply(&mut unit);
+            state.register_unit(name, unit);
+        }
+        for (name, feature) in &self.feature_catalog {
+            state.register_feature(name, feature.clone());
+        }
+
+        if let Some(map) = &self.map {
+            state.set_map(map.clone());
+        }
+        state.map.geo_vents.extend(self.geo_vents.iter().copied());
+        if let Some(wind_strength) = self.wind_strength {
+            state.wind_strength = wind_strength;
+        }
+        if let Some(tidal_strength) = self.tidal_strength {
+            state.tidal_strength = tidal_strength;
+        }
+
+        for feature in &self.starting_features {
+            state.add_feature(&feature.name, feature.position)?;
+        }
+        for starting_unit in &self.starting_units {
+            let unit_idx = state.add_unit(&starting_unit.name)?;
+            let unit = &mut state.units[unit_idx];
+            if starting_unit.progress >= 1.0 {
+                unit.construct();
+            } else {
+                unit.metal = starting_unit.progress * unit.m_build_cost;
+                unit.energy = starting_unit.progress * unit.e_build_cost;
+            }
+            if starting_unit.position.is_some() {
+                unit.position = starting_unit.position;
+            }
+        }
+        Ok(state)
+    }
+
+
+    // Runs the build order for the duration of the scenario.
+    pub fn run(&self) -> Result<(GameState, BuildOrderReport), Box<dyn Error>> {
+        self.run_recorded(&mut [])
+    }
+
+
+    // Runs the build order for the duration of the scenario, showing every step to the observers.
+    pub fn run_recorded(&self, observers: &mut [&mut dyn Observer]) -> Result<(GameState, BuildOrderReport), Box<dyn Error>> {
+        let mut state = self.create_state()?;
+        let mut runner = BuildOrderRunner::new(self.build_order.clone(), &state)?;
+        for observer in observers.iter_mut() {
+            observer.record(&state);
+        }
+        while state.time < self.duration {
+            runner.step(&mut state, self.time_step);
+            for observer in observers.iter_mut() {
+                observer.record(&state);
+            }
+        }
+        let report = runner.report(&state);
+        Ok((state, report))
+    }
+}
+
+
+// Reads a list of strings, also accepting a single string.
+fn get_string_list(lua: &Lua, map: &HashMap<String, Value>, key: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(match map.get(key) {
+        Some(Value::String(s)) => vec![s.to_string_lossy()],
+        Some(v) => Vec::<String>::from_lua(v.clone(), lua)?,
+        None => Vec::new(),
+    })
+}
+
+
+fn get_table(lua: &Lua, map: &HashMap<String, Value>, key: &str) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    Ok(match map.get(key) {
+        Some(v) => HashMap::<String, Value>::from_lua(v.clone(), lua)?,
+        None => HashMap::new(),
+    })
+}
+
+
+fn get_list(lua: &Lua, map: &HashMap<String, Value>, key: &str) -> Result<Vec<HashMap<String, Value>>, Box<dyn Error>> {
+    Ok(match map.get(key) {
+        Some(v) => Vec::<HashMap<String, Value>>::from_lua(v.clone(), lua)?,
+        None => Vec::new(),
+    })
+}
+
+
+fn get_optional_float(map: &HashMap<String, Value>, key: &str) -> Result<Option<f32>, Box<dyn Error>> {
+    Ok(match map.contains_key(key) {
+        true => Some(get_float_or(map, key, 0.0)?),
+        false => None,
+    })
+}
+
+
+// Positions are written as `{ x = 100, z = 200 }`.
+fn parse_position(lua: &Lua, value: &Value) -> Result<Position, Box<dyn Error>> {
+    let map = HashMap::<String, Value>::from_lua(value.clone(), lua)?;
+    Ok(Position::new(get_float_or(&map, "x", 0.0)?, get_float_or(&map, "z", 0.0)?))
+}
+
+
+fn get_position(lua: &Lua, map: &HashMap<String, Value>) -> Result<Option<Position>, Box<dyn Error>> {
+    map.get("position").map(|value| parse_position(lua, value)).transpose()
+}
+
+
+pub fn load_scenario_from_path(scenario_path: &Path) -> Result<Scenario, Box<dyn Error>> {
+    let scenario_str = fs::read_to_string(scenario_path)?;
+    let base_path = scenario_path.parent().unwrap_or(Path::new("."));
+    let mut scenario = parse_scenario(&scenario_str, base_path)?;
+    if scenario.name == "Unknown" {
+        scenario.name = scenario_path.file_stem().unwrap().display().to_string()
+    }
+    Ok(scenario)
+}
+
+
+// Parses a scenario. Paths to the catalog, feature definitions and map are relative to the base path.
+pub fn parse_scenario(scenario: &str, base_path: &Path) -> Result<Scenario, Box<dyn Error>> {
+    let lua = Lua::new();
+    let defs: HashMap<String, Value> = lua.load(scenario).eval()?;
+
+    let mut catalog = HashMap::new();
+    for catalog_path in get_string_list(&lua, &defs, "catalog")? {
+        catalog.extend(load_catalog_from_path(&base_path.join(catalog_path))?);
+    }
+    let mut feature_catalog = HashMap::new();
+    for featuredefs_path in get_string_list(&lua, &defs, "featuredefs")? {
+        feature_catalog.extend(load_feature_definitions_from_path(&base_path.join(featuredefs_path))?);
+    }
+    let map = match defs.get("map") {
+        Some(_) => Some(load_map_from_path(&base_path.join(get_string_or(&defs, "map", "")?))?),
+        None => None,
+    };
+
+    let options = get_table(&lua, &defs, "modoptions")?;
+    let default_options = ModOptions::default();
+    let mod_options = ModOptions {
+        resource_income: get_float_or(&options, "multiplier_resourceincome", default_options.resource_income)?,
+        energy_production: get_float_or(&options, "multiplier_energyproduction", default_options.energy_production)?,
+        build_power: get_float_or(&options, "multiplier_buildpower", default_options.build_power)?,
+        build_distance: get_float_or(&options, "multiplier_builddistance", default_options.build_distance)?,
+        metal_cost: get_float_or(&options, "multiplier_metalcost", default_options.metal_cost)?,
+        energy_cost: get_float_or(&options, "multiplier_energycost", default_options.energy_cost)?,
+        buildtime_cost: get_float_or(&options, "multiplier_buildtimecost", default_options.buildtime_cost)?,
+    };
+
+    let world = get_table(&lua, &defs, "world")?;
+    let default_params = WorldParams::default();
+    let world_params = WorldParams {
+        decay_delay: get_float_or(&world, "decay_delay", default_params.decay_delay)?,
+        decay_rate: get_float_or(&world, "decay_rate", default_params.decay_rate)?,
+        start_metal: get_float_or(&world, "start_metal", default_params.start_metal)?,
+        base_metal_storage: get_float_or(&world, "base_metal_storage", default_params.base_metal_storage)?,
+        start_energy: get_float_or(&world, "start_energy", default_params.start_energy)?,
+        base_energy_storage: get_float_or(&world, "base_energy_storage", default_params.base_energy_storage)?,
+    };
+
+    let geo_vents = match defs.get("geo_vents") {
+        Some(v) => Vec::<Value>::from_lua(v.clone(), &lua)?.iter().map(|v| parse_position(&lua, v)).collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+    let mut starting_units = Vec::new();
+    for unit in get_list(&lua, &defs, "units")? {
+        starting_units.push(StartingUnit {
+            name: get_string_or(&unit, "name", "")?,
+            progress: get_float_or(&unit, "progress", 1.0)?,
+            position: get_position(&lua, &unit)?,
+        });
+    }
+    let mut starting_features = Vec::new();
+    for feature in get_list(&lua, &defs, "features")? {
+        starting_features.push(StartingFeature {
+            name: get_string_or(&feature, "name", "")?,
+            position: get_position(&lua, &feature)?,
+        });
+    }
+
+    let time_step = get_float_or(&defs, "time_step", 0.1)?;
+    validate_time_step(time_step)?;
+    let duration = get_float_or(&defs, "duration", 300.0)?;
+    if !duration.is_finite() {
+        return Err(format!("Invalid duration {}.", duration).into())
+    }
+
+    Ok(Scenario {
+        name: get_string_or(&defs, "name", "Unknown")?,
+        catalog,
+        feature_catalog,
+        mod_options,
+        world_params,
+        map,
+        geo_vents,
+        wind_strength: get_optional_float(&defs, "wind")?,
+        tidal_strength: get_optional_float(&defs, "tidal")?,
+        starting_units,
+        starting_features,
+        build_order: BuildOrder::parse(&get_string_or(&defs, "build_order", "")?)?,
+        time_step,
+        duration,
+    })
+}
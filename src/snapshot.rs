@@ -0,0 +1,126 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_state::GameState;
+
+// Increased whenever a change to the game state makes older snapshots unreadable.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+// Binary snapshots start with these bytes, followed by the bincode encoded snapshot.
+const BINARY_MAGIC: &[u8; 4] = b"RBAR";
+
+
+// A saved game state, including the unit catalog, so that it can be restored without the original definitions.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub state: GameState,
+}
+
+
+impl Snapshot {
+    pub fn new(state: &GameState) -> Snapshot {
+        Snapshot { version: SNAPSHOT_VERSION, state: state.clone() }
+    }
+
+
+    fn into_state(self) -> Result<GameState, Box<dyn Error>> {
+        if self.version != SNAPSHOT_VERSION {
+            return Err(format!("Snapshot version {} is not supported, expected version {}.", self.version, SNAPSHOT_VERSION).into())
+        }
+        Ok(self.state)
+    }
+}
+
+
+pub fn to_json(state: &GameState) -> Result<String, Box<dyn Error>> {
+    Ok(serde_json::to_string_pretty(&Snapshot::new(state))?)
+}
+
+
+pub fn from_json(json: &str) -> Result<GameState, Box<dyn Error>> {
+    serde_json::from_str::<Snapshot>(json)?.into_state()
+}
+
+
+pub fn to_binary(state: &GameState) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = BINARY_MAGIC.to_vec();
+    bincode::serialize_into(&mut bytes, &Snapshot::new(state))?;
+    Ok(bytes)
+}
+
+
+pub fn from_binary(bytes: &[u8]) -> Result<GameState, Box<dyn Error>> {
+    let snapshot = bytes.strip_prefix(BINARY_MAGIC).ok_or("Not a binary snapshot.")?;
+    bincode::deserialize::<Snapshot>(snapshot)?.into_state()
+}
+
+
+// Saves the state as JSON if the file ends in .json and in the binary format otherwise.
+pub fn save_snapshot(state: &GameState, path: &Path) -> Result<(), Box<dyn Error>> {
+    match path.extension().is_some_and(|extension| extension == "json") {
+        true => fs::write(path, to_json(state)?)?,
+        false => fs::write(path, to_binary(state)?)?,
+    }
+    Ok(())
+}
+
+
+// Loads a snapshot in either format.
+pub fn load_snapshot(path: &Path) -> Result<GameState, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    match bytes.starts_with(BINARY_MAGIC) {
+        true => from_binary(&bytes),
+        false => from_json(std::str::from_utf8(&bytes)?),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+    use crate::map::Position;
+    use crate::unit::Unit;
+    use crate::world_params::WorldParams;
+
+    #[test]
+    fn test_round_trip() {
+        let mut state = GameState::new(WorldParams::default());
+        let mut com = Unit::new_unconstructed(1.0, 1.0, 1.0);
+        com.buildpower = 100.0;
+        com.speed = 30.0;
+        com.e_per_second = 25.0;
+        com.build_options.insert("wind".to_string());
+        state.register_unit("commander", com);
+        let mut wind = Unit::new_unconstructed(40.0, 175.0, 1600.0);
+        wind.wind_e_per_second = 25.0;
+        state.register_unit("wind", wind);
+        let com_idx = state.add_completed_unit("commander").unwrap();
+        state.units[com_idx].position = Some(Position::new(0.0, 0.0));
+        state.build_unit(com_idx, "wind").unwrap();
+        state.queue_command(com_idx, Command::Build { unit: "wind".to_string(), position: Some(Position::new(200.0, 0.0)) }).unwrap();
+        state.simulate(5.0);
+
+        let restored = from_json(&to_json(&state).unwrap()).unwrap();
+        assert_eq!(restored, state);
+        let json = to_json(&state).unwrap().replace(&format!("\"version\": {}", SNAPSHOT_VERSION), "\"version\": 0");
+        assert!(from_json(&json).is_err());
+
+        let bytes = to_binary(&state).unwrap();
+        assert!(bytes.len() < to_json(&state).unwrap().len());
+        let mut restored = from_binary(&bytes).unwrap();
+        assert_eq!(restored, state);
+        assert!(from_binary(&bytes[4..]).is_err());
+
+        // The restored state continues exactly like the original.
+        for _ in 0..100 {
+            state.simulate(0.1);
+            restored.simulate(0.1);
+        }
+        assert_eq!(restored, state);
+    }
+}
@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+// Statistics about resource shortages, either for a single timestep or summed up over the game.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Stalls {
+    // Time during which at least one consumer did not get the energy or metal it requested
+    pub energy_stall_time: f32,
+    pub metal_stall_time: f32,
+    // Build power multiplied by the time it could not be used due to missing resources
+    pub build_power_lost: f32,
+    // Time each unit spent without the resources it needed. Keyed by the index in the world unit list.
+    pub starved_units: HashMap<usize, f32>,
+}
+
+
+impl Stalls {
+    pub fn add(&mut self, other: &Stalls) {
+        self.energy_stall_time += other.energy_stall_time;
+        self.metal_stall_time += other.metal_stall_time;
+        self.build_power_lost += other.build_power_lost;
+        for (unit_idx, time) in &other.starved_units {
+            *self.starved_units.entry(*unit_idx).or_default() += time;
+        }
+    }
+
+
+    pub fn is_stalled(&self) -> bool {
+        self.energy_stall_time > 0.0 || self.metal_stall_time > 0.0
+    }
+
+
+    // Starved units, starting with the one that was starved the longest.
+    pub fn most_starved(&self) -> Vec<(usize, f32)> {
+        let mut starved: Vec<(usize, f32)> = self.starved_units.iter().map(|(idx, time)| (*idx, *time)).collect();
+        starved.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        starved
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt::Write;
+
+use crate::unit::Unit;
+
+// Which unit can build which, as given by the build options of the catalog.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct TechTree {
+    // Build options of every unit in the catalog. Options that are not in the catalog are left out.
+    pub edges: BTreeMap<String, BTreeSet<String>>,
+}
+
+
+impl TechTree {
+    pub fn from_catalog(catalog: &HashMap<String, Unit>) -> TechTree {
+        let edges = catalog.iter().map(|(name, unit)| {
+            let options = unit.build_options.iter().filter(|option| catalog.contains_key(*option)).cloned().collect();
+            (name.clone(), options)
+        }).collect();
+        TechTree { edges }
+    }
+
+
+    pub fn contains(&self, unit: &str) -> bool {
+        self.edges.contains_key(unit)
+    }
+
+
+    // Units that have the unit in their build options, sorted by name.
+    pub fn builders_of(&self, unit: &str) -> Vec<&str> {
+        self.edges.iter().filter(|(_, options)| options.contains(unit)).map(|(builder, _)| builder.as_str()).collect()
+    }
+
+
+    // Units that can be built directly or through a chain of builders, including the starting units themselves.
+    pub fn reachable_from(&self, starts: &[&str]) -> BTreeSet<String> {
+        let mut reachable = BTreeSet::new();
+        let mut queue: VecDeque<&str> = starts.iter().copied().filter(|start| self.contains(start)).collect();
+        while let Some(unit) = queue.pop_front() {
+            if reachable.insert(unit.to_string()) {
+                queue.extend(self.edges[unit].iter().map(String::as_str));
+            }
+        }
+        reachable
+    }
+
+
+    // Units of the catalog that none of the starting units can get to.
+    pub fn unreachable_from(&self, starts: &[&str]) -> Vec<&str> {
+        let reachable = self.reachable_from(starts);
+        self.edges.keys().filter(|unit| !reachable.contains(*unit)).map(String::as_str).collect()
+    }
+
+
+    // Shortest chain of builders from one unit to another, both included.
+    // For example commander -> lab -> constructor -> advanced lab.
+    pub fn shortest_chain(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(unit) = queue.pop_front() {
+            if unit == to {
+                let mut chain = vec![to.to_string()];
+                let mut current = to;
+                while let Some(builder) = previous.get(current) {
+                    chain.push(builder.to_string());
+                    current = builder;
+                }
+                chain.reverse();
+                return Some(chain);
+            }
+            for option in self.edges.get(unit).into_iter().flatten() {
+                if option != from && !previous.contains_key(option.as_str()) {
+                    previous.insert(option, unit);
+                    queue.push_back(option);
+                }
+            }
+        }
+        None
+    }
+
+
+    // Graphviz graph with an arrow from every builder to the units it can build.
+    // Units that can neither build nor be built are left out.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tech_tree {\n    rankdir=LR;\n    node [shape=box];\n");
+        for (builder, options) in &self.edges {
+            if options.is_empty() && self.builders_of(builder).is_empty() {
+                continue;
+            }
+            writeln!(dot, "    \"{}\";", builder).unwrap();
+            for option in options {
+                writeln!(dot, "    \"{}\" -> \"{}\";", builder, option).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_catalog() -> HashMap<String, Unit> {
+        let mut catalog = HashMap::new();
+        let mut add = |name: &str, options: &[&str]| {
+            let mut unit = Unit::new_unconstructed(1.0, 1.0, 1.0);
+            unit.build_options = options.iter().map(|option| option.to_string()).collect();
+            catalog.insert(name.to_string(), unit);
+        };
+        add("commander", &["mex", "lab", "missing"]);
+        add("lab", &["con"]);
+        add("con", &["mex", "t2_lab"]);
+        add("t2_lab", &["t2_con"]);
+        add("t2_con", &["mex"]);
+        add("mex", &[]);
+        add("scav_lab", &["mex"]);
+        catalog
+    }
+
+
+    #[test]
+    fn test_queries() {
+        let tree = TechTree::from_catalog(&create_catalog());
+        assert!(!tree.edges["commander"].contains("missing"));
+        assert_eq!(tree.builders_of("mex"), vec!["commander", "con", "scav_lab", "t2_con"]);
+        assert_eq!(tree.shortest_chain("commander", "t2_con").unwrap(), vec!["commander", "lab", "con", "t2_lab", "t2_con"]);
+        assert_eq!(tree.shortest_chain("commander", "mex").unwrap(), vec!["commander", "mex"]);
+        assert_eq!(tree.shortest_chain("commander", "commander").unwrap(), vec!["commander"]);
+        assert!(tree.shortest_chain("mex", "lab").is_none());
+        assert_eq!(tree.unreachable_from(&["commander"]), vec!["scav_lab"]);
+        assert_eq!(tree.reachable_from(&["lab"]).len(), 5);
+    }
+
+
+    #[test]
+    fn test_dot() {
+        let tree = TechTree::from_catalog(&create_catalog());
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph tech_tree {"));
+        assert!(dot.contains("    \"commander\" -> \"lab\";\n"));
+        assert_eq!(dot.matches("->").count(), 8);
+    }
+}
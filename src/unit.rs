@@ -1,9 +1,20 @@
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 
-#[derive(PartialEq, Clone, Debug)]
+use serde::{Deserialize, Serialize};
+
+use crate::command::Command;
+use crate::feature::Feature;
+use crate::map::Position;
+
+// The engine does not allow builders to have a shorter build range than this.
+pub const DEFAULT_BUILD_DISTANCE: f32 = 128.0;
+
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct Unit {
     // Status
     pub name: String,
+    pub def_name: String, // Name of the unit in the catalog
     pub alive: bool,
     pub metal: f32,
     pub energy: f32,
@@ -15,6 +26,14 @@ pub struct Unit {
     pub buildpower: f32,
     pub build_target: Option<usize>, // Points to target in world unit list
     pub build_options: HashSet<String>,
+    pub reclaim_speed: f32,
+    pub reclaim_target: Option<usize>, // Points to target in world feature list
+    pub commands: VecDeque<Command>,
+
+    // Movement. Units without a position ignore distances entirely.
+    pub position: Option<Position>,
+    pub speed: f32, // elmos per second
+    pub build_distance: f32,
 
     // Unit construction
     pub buildtime: f32,
@@ -25,9 +44,17 @@ pub struct Unit {
     pub e_cost_per_second: f32,
     pub e_per_second: f32,
     pub wind_e_per_second: f32,
+    pub tidal_e_multiplier: f32, // Produces this multiple of the map's tidal strength
     pub e_storage: f32,
     pub m_per_second: f32,
     pub m_storage: f32,
+
+    // The wreck that is left behind when the unit is destroyed.
+    pub corpse: Option<Feature>,
+
+    // Placement
+    pub needs_geo: bool,
+    pub geo_vent: Option<usize>, // Points to the occupied vent in the map's geo vent list
 }
 
 
@@ -36,21 +63,32 @@ impl Unit {
     pub fn new_unconstructed(m_cost: f32, e_cost: f32, buildtime: f32) -> Unit {
         Unit {
             name: "Unnamed".to_string(),
+            def_name: String::new(),
             alive: false,
             metal: 0.0,
             energy: 0.0,
             buildpower: 0.0,
             build_target: None,
             build_options: HashSet::new(),
+            reclaim_speed: 0.0,
+            reclaim_target: None,
+            commands: VecDeque::new(),
+            position: None,
+            speed: 0.0,
+            build_distance: DEFAULT_BUILD_DISTANCE,
             buildtime,
             m_build_cost: m_cost,
             e_build_cost: e_cost,
             e_cost_per_second: 0.0,
             e_per_second: 0.0,
             wind_e_per_second: 0.0,
+            tidal_e_multiplier: 0.0,
             e_storage: 0.0,
             m_per_second: 0.0,
             m_storage: 0.0,
+            corpse: None,
+            needs_geo: false,
+            geo_vent: None,
         }
     }
 
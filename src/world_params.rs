@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 // Contains parameters that affect all units, like global decay rate.
-#[derive(Clone)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct WorldParams {
     pub decay_delay: f32,
     pub decay_rate: f32,
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use approx::assert_abs_diff_eq;
+use rebar::catalog_diff::{compare_timings, diff_catalogs};
+use rebar::loader::load_catalog_from_path;
+use rebar::scenario::load_scenario_from_path;
+
+#[test]
+fn wind_nerf() {
+    let old = load_catalog_from_path(&PathBuf::from("tests/unitdefs")).unwrap();
+    let mut new = old.clone();
+    new.get_mut("armwin").unwrap().buildtime *= 2.0;
+
+    let diff = diff_catalogs(&old, &new);
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].unit, "armwin");
+    assert_eq!(diff.changed[0].fields[0].field, "buildtime");
+
+    // Both winds of the opening take twice as long.
+    let scenario = load_scenario_from_path(&PathBuf::from("tests/scenarios/WindOpening.lua")).unwrap();
+    let shift = compare_timings(&scenario, &old, &new).unwrap();
+    assert_eq!(shift.steps.len(), 2);
+    for step in &shift.steps {
+        assert_eq!(step.unit, "armwin");
+        assert!(step.new.unwrap() > step.old.unwrap());
+    }
+    assert_abs_diff_eq!(shift.steps[0].new.unwrap(), 2.0 * shift.steps[0].old.unwrap(), epsilon = 1.0);
+}
@@ -0,0 +1,39 @@
+use std::fs;
+use std::path::PathBuf;
+
+use rebar::lint::{lint_catalog_from_path, Severity};
+
+#[test]
+fn lint_unitdefs() {
+    let report = lint_catalog_from_path(&PathBuf::from("tests/unitdefs")).unwrap();
+    let messages: Vec<String> = report.issues.iter()
+        .map(|issue| format!("{} {}: {}", issue.severity, issue.unit, issue.message))
+        .collect();
+    // The solar is keyed by its file name, so the commander cannot find it either.
+    assert_eq!(messages, vec![
+        "error Commander: build option 'armlab' is not in the catalog",
+        "error Commander: build option 'armmex' is not in the catalog",
+        "error Commander: build option 'armsolar' is not in the catalog",
+        "warning Solar: negative energyupkeep (-20) without energymake is counted as energy production",
+    ]);
+}
+
+
+#[test]
+fn lint_duplicates() {
+    // Unique per process, so that concurrent test runs do not share the directory.
+    let dir = std::env::temp_dir().join(format!("rebar_lint_duplicates_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("legacy")).unwrap();
+    fs::copy("tests/unitdefs/WindGenerator.lua", dir.join("WindGenerator.lua")).unwrap();
+    fs::copy("tests/unitdefs/WindGenerator.lua", dir.join("legacy/WindGenerator.lua")).unwrap();
+    fs::write(dir.join("Broken.lua"), "return { broken = { buildtime = 10 } }").unwrap();
+
+    let report = lint_catalog_from_path(&dir).unwrap();
+    assert_eq!(report.count(Severity::Error), 2);
+    assert_eq!(report.count(Severity::Warning), 0);
+    assert!(report.issues[0].message.contains("Required key"));
+    assert_eq!(report.issues[1].unit, "armwin");
+    assert!(report.issues[1].message.starts_with("defined in 2 files"));
+    fs::remove_dir_all(&dir).unwrap();
+}
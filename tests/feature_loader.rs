@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use rebar::{feature::Feature, loader::load_feature_definitions_from_path};
+
+#[test]
+fn load_rocks() {
+    let feature_def_path = PathBuf::from("tests/featuredefs/Rocks.lua");
+    let features = load_feature_definitions_from_path(&feature_def_path).unwrap();
+    assert_eq!(features.len(), 2);
+
+    let mut rock = Feature::new(15.0, 0.0, 600.0);
+    rock.name = "rock_small".to_string();
+    assert_eq!(features["rock_small"], rock);
+
+    // Without a reclaim time, the engine derives one from the value of the feature.
+    let mut tree = Feature::new(0.0, 40.0, 240.0);
+    tree.name = "tree_pine".to_string();
+    assert_eq!(features["tree_pine"], tree);
+}
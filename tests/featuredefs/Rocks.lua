@@ -0,0 +1,28 @@
+-- Credits to Beyond All Reason team
+return {
+	rock_small = {
+		blocking = true,
+		category = "rocks",
+		damage = 500,
+		footprintx = 2,
+		footprintz = 2,
+		height = 16,
+		metal = 15,
+		object = "Features/rock_small.s3o",
+		reclaimable = true,
+		reclaimtime = 600,
+		world = "allworld",
+	},
+	tree_pine = {
+		blocking = true,
+		category = "vegetation",
+		damage = 50,
+		energy = 40,
+		footprintx = 1,
+		footprintz = 1,
+		height = 40,
+		object = "Features/tree_pine.s3o",
+		reclaimable = true,
+		world = "allworld",
+	},
+}
@@ -0,0 +1,25 @@
+use std::path::PathBuf;
+
+use approx::assert_abs_diff_eq;
+use rebar::map_loader::load_map_from_path;
+
+#[test]
+fn load_test_map() {
+    let map_path = PathBuf::from("tests/maps/TestMap");
+    let map = load_map_from_path(&map_path).unwrap();
+
+    assert_eq!(map.name, "Test Map");
+    assert_abs_diff_eq!(map.min_wind, 7.0);
+    assert_abs_diff_eq!(map.max_wind, 21.0);
+    assert_abs_diff_eq!(map.average_wind(), 14.0);
+    assert_abs_diff_eq!(map.tidal_strength, 18.0);
+    assert_abs_diff_eq!(map.max_metal, 2.0);
+
+    assert_eq!(map.metal_spots.len(), 2);
+    assert_abs_diff_eq!(map.metal_spots[0].metal, 8.0);
+    assert_abs_diff_eq!(map.metal_spots[0].position.x, 32.0, epsilon = 1e-3);
+    assert_abs_diff_eq!(map.metal_spots[0].position.z, 32.0, epsilon = 1e-3);
+    assert_abs_diff_eq!(map.metal_spots[1].metal, 0.4);
+    assert_abs_diff_eq!(map.metal_spots[1].position.x, 104.0, epsilon = 1e-3);
+    assert_abs_diff_eq!(map.metal_spots[1].position.z, 88.0, epsilon = 1e-3);
+}
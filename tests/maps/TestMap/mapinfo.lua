@@ -0,0 +1,39 @@
+local mapinfo = {
+	name        = "Test Map",
+	shortname   = "TM",
+	description = "Small map for testing the map loader",
+	author      = "rebar",
+	version     = "1.0",
+	modtype     = 3,
+	depend      = {"Map Helper v1"},
+	replace     = {},
+
+	maphardness     = 100,
+	notDeformable   = false,
+	gravity         = 130,
+	tidalStrength   = 18,
+	maxMetal        = 2.0,
+	extractorRadius = 90.0,
+	voidWater       = false,
+	autoShowMetal   = true,
+
+	smf = {
+		minheight = -100,
+		maxheight = 400,
+	},
+
+	atmosphere = {
+		minWind      = 7,
+		maxWind      = 21,
+		fogStart     = 0.8,
+		fogEnd       = 1.0,
+		fogColor     = {0.7, 0.7, 0.8},
+	},
+}
+
+local cfg = VFS.FileExists("mapconfig/mapinfo_cfg.lua") and VFS.Include("mapconfig/mapinfo_cfg.lua") or {}
+for key, value in pairs(cfg) do
+	mapinfo[key] = value
+end
+
+return mapinfo
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use rebar::monte_carlo::{run_monte_carlo, Metric, MonteCarloConfig};
+use rebar::scenario::load_scenario_from_path;
+
+#[test]
+fn wind_opening() {
+    let scenario = load_scenario_from_path(&PathBuf::from("tests/scenarios/WindOpening.lua")).unwrap();
+    let metrics = [Metric::FinishTime("armwin".to_string()), Metric::EnergyIncome(20.0), Metric::FinishTime("armgeo".to_string())];
+    let config = MonteCarloConfig { runs: 20, seed: 3, threads: 2, ..MonteCarloConfig::default() };
+    let result = run_monte_carlo(&scenario, &metrics, &config).unwrap();
+    assert_eq!(result.runs, 20);
+
+    // Without command delays, only the wind varies, which does not change when the winds are finished.
+    let finish = &result.distributions[0];
+    assert_eq!(finish.values.len(), 20);
+    assert_eq!(finish.percentile(10.0), finish.percentile(90.0));
+    // The map's wind is between 7 and 21, which the two finished winds turn into different incomes.
+    // The half-built wind of the start is never finished.
+    let income = &result.distributions[1];
+    assert!(income.percentile(10.0).unwrap() < income.percentile(90.0).unwrap());
+    assert!(income.values.iter().all(|income| (30.0 + 2.0 * 7.0..=30.0 + 2.0 * 21.0).contains(income)));
+    // The build order does not contain a geothermal.
+    assert_eq!(result.distributions[2].missing, 20);
+
+    // Delays only make the opening slower. The same seed gives the same result for any number of threads.
+    let delayed_config = MonteCarloConfig { max_command_delay: 3.0, ..config };
+    let delayed = run_monte_carlo(&scenario, &metrics, &delayed_config).unwrap();
+    assert!(delayed.distributions[0].percentile(50.0).unwrap() > finish.percentile(50.0).unwrap());
+    let single_threaded = run_monte_carlo(&scenario, &metrics, &MonteCarloConfig { threads: 1, ..delayed_config }).unwrap();
+    assert_eq!(single_threaded, delayed);
+}
+
+
+#[test]
+fn invalid_settings() {
+    let scenario = load_scenario_from_path(&PathBuf::from("tests/scenarios/WindOpening.lua")).unwrap();
+    let metrics = [Metric::CompletionTime];
+    let config = MonteCarloConfig { runs: 2, threads: 1, ..MonteCarloConfig::default() };
+    for invalid in [
+        MonteCarloConfig { max_command_delay: f32::INFINITY, ..config.clone() },
+        MonteCarloConfig { max_command_delay: f32::NAN, ..config.clone() },
+        MonteCarloConfig { sample_interval: 0.0, ..config.clone() },
+        MonteCarloConfig { wind_interval: Some(-1.0), ..config.clone() },
+    ] {
+        assert!(run_monte_carlo(&scenario, &metrics, &invalid).is_err());
+    }
+    let mut scenario = scenario;
+    scenario.time_step = 0.0;
+    assert!(run_monte_carlo(&scenario, &metrics, &config).is_err());
+}
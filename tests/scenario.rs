@@ -0,0 +1,78 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use approx::assert_abs_diff_eq;
+use rebar::map::Position;
+use rebar::recorder::Recorder;
+use rebar::scenario::{load_scenario_from_path, parse_scenario};
+
+#[test]
+fn load_wind_opening() {
+    let scenario_path = PathBuf::from("tests/scenarios/WindOpening.lua");
+    let scenario = load_scenario_from_path(&scenario_path).unwrap();
+    assert_eq!(scenario.name, "WindOpening");
+    assert!(scenario.catalog.contains_key("Commander"));
+    assert!(scenario.catalog.contains_key("armwin"));
+    assert!(scenario.catalog.contains_key("armgeo"));
+
+    let state = scenario.create_state().unwrap();
+    assert_abs_diff_eq!(state.metal, 800.0);
+    assert_abs_diff_eq!(state.energy, 1000.0);
+    assert_abs_diff_eq!(state.wind_strength, 12.0);
+    assert_abs_diff_eq!(state.tidal_strength, 18.0);
+    assert_eq!(state.map.metal_spots.len(), 2);
+    assert_eq!(state.map.geo_vents, vec![Position::new(500.0, 500.0)]);
+    assert_eq!(state.features.len(), 1);
+
+    // Starting units, with the build power mod option applied
+    assert_eq!(state.units.len(), 2);
+    assert!(state.units[0].alive);
+    assert_abs_diff_eq!(state.units[0].buildpower, 600.0);
+    assert_eq!(state.units[0].position, Some(Position::new(100.0, 100.0)));
+    assert!(!state.units[1].alive);
+    assert_abs_diff_eq!(state.units[1].metal, 20.0);
+}
+
+
+#[test]
+fn run_wind_opening() {
+    let scenario_path = PathBuf::from("tests/scenarios/WindOpening.lua");
+    let scenario = load_scenario_from_path(&scenario_path).unwrap();
+    let (state, report) = scenario.run().unwrap();
+
+    assert!(report.is_complete());
+    assert_abs_diff_eq!(state.time, 30.0, epsilon = 1e-3);
+    // Each wind takes 1600 / 600 seconds to build.
+    assert_abs_diff_eq!(report.finish_time().unwrap(), 5.4, epsilon = 0.11);
+    assert_abs_diff_eq!(state.energy_production(), 30.0 + 2.0 * 12.0);
+}
+
+
+#[test]
+fn record_wind_opening() {
+    let scenario_path = PathBuf::from("tests/scenarios/WindOpening.lua");
+    let scenario = load_scenario_from_path(&scenario_path).unwrap();
+    let mut recorder = Recorder::new(5.0).unwrap();
+    scenario.run_recorded(&mut [&mut recorder]).unwrap();
+
+    assert_eq!(recorder.samples.len(), 7);
+    assert_abs_diff_eq!(recorder.samples[0].build_power_used, 0.0);
+    assert_abs_diff_eq!(recorder.samples[1].build_power_used, 600.0);
+    assert_abs_diff_eq!(recorder.samples[6].energy_income, 30.0 + 2.0 * 12.0);
+}
+
+
+#[test]
+fn reject_invalid_time() {
+    let scenario = fs::read_to_string("tests/scenarios/WindOpening.lua").unwrap();
+    let base_path = Path::new("tests/scenarios");
+    // A step that does not advance the time would never reach the end of the scenario.
+    for time_step in ["0", "-0.1", "0/0"] {
+        let invalid = scenario.replace("duration = 30,", &format!("duration = 30, time_step = {},", time_step));
+        assert!(parse_scenario(&invalid, base_path).is_err());
+    }
+    let invalid = scenario.replace("duration = 30,", "duration = math.huge,");
+    assert!(parse_scenario(&invalid, base_path).is_err());
+    let valid = scenario.replace("duration = 30,", "duration = 30, time_step = 0.5,");
+    assert!(parse_scenario(&valid, base_path).is_ok());
+}
@@ -0,0 +1,24 @@
+return {
+	catalog = "../unitdefs",
+	featuredefs = "../featuredefs/Rocks.lua",
+	map = "../maps/TestMap",
+	modoptions = {
+		multiplier_buildpower = 2,
+	},
+	world = {
+		start_metal = 800,
+	},
+	wind = 12,
+	geo_vents = {
+		{ x = 500, z = 500 },
+	},
+	units = {
+		{ name = "Commander", position = { x = 100, z = 100 } },
+		{ name = "armwin", progress = 0.5 },
+	},
+	features = {
+		{ name = "rock_small", position = { x = 150, z = 100 } },
+	},
+	build_order = "Commander: armwin x2",
+	duration = 30,
+}
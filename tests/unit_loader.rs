@@ -1,6 +1,15 @@
+use std::collections::VecDeque;
 use std::path::PathBuf;
 
-use rebar::{loader::load_definition_from_path, unit::Unit};
+use rebar::{feature::Feature, loader::load_definition_from_path, unit::Unit};
+
+// The wrecks in the test definitions only contain metal.
+fn wreck(metal: f32) -> Feature {
+    let mut feature = Feature::new(metal, 0.0, Feature::default_reclaim_time(metal, 0.0));
+    feature.name = "dead".to_string();
+    feature
+}
+
 
 #[test]
 fn load_wind() {
@@ -12,6 +21,7 @@ fn load_wind() {
     expected.name = "WindGenerator".to_string();
     expected.wind_e_per_second = 25.0;
     expected.e_storage = 0.5;
+    expected.corpse = Some(wreck(23.0));
     
     assert_eq!(unit, expected);
 }
@@ -25,6 +35,22 @@ fn load_solar() {
     expected.name = "Basic Solar".to_string();
     expected.e_per_second = 20.0;
     expected.e_storage = 50.0;
+    expected.corpse = Some(wreck(75.0));
+    
+    assert_eq!(unit, expected);
+}
+
+
+#[test]
+fn load_geothermal() {
+    let unit_def_path = PathBuf::from("tests/unitdefs/Geothermal.lua"); 
+    let unit = load_definition_from_path(&unit_def_path).unwrap();
+    let mut expected = Unit::new_unconstructed(560.0, 13000.0, 13100.0);
+    expected.name = "Geothermal".to_string();
+    expected.e_per_second = 300.0;
+    expected.e_storage = 1000.0;
+    expected.needs_geo = true;
+    expected.corpse = Some(wreck(300.0));
     
     assert_eq!(unit, expected);
 }
@@ -36,21 +62,32 @@ fn load_commander() {
     let unit = load_definition_from_path(&unit_def_path).unwrap();
     let expected = Unit {
         name: "Commander".to_string(),
+        def_name: String::new(),
         alive: false,
         metal: 0.0,
         energy: 0.0,
         buildpower: 300.0,
         build_target: None,
         build_options: vec!["armwin", "armsolar", "armmex", "armlab"].into_iter().map(str::to_owned).collect(),
+        reclaim_speed: 300.0,
+        reclaim_target: None,
+        commands: VecDeque::new(),
+        position: None,
+        speed: 37.5,
+        build_distance: 145.0,
         buildtime: 75000.0,
         m_build_cost: 2700.0,
         e_build_cost: 26000.0,
         e_cost_per_second: 0.0,
         e_per_second: 30.0,
         wind_e_per_second: 0.0,
+        tidal_e_multiplier: 0.0,
         e_storage: 500.0,
         m_per_second: 2.0,
         m_storage: 500.0,
+        corpse: Some(wreck(1250.0)),
+        needs_geo: false,
+        geo_vent: None,
     };
     
     assert_eq!(unit, expected);
@@ -0,0 +1,72 @@
+-- Credits to Beyond All Reason team
+return {
+	armgeo = {
+		activatewhenbuilt = true,
+		buildangle = 0,
+		buildpic = "ARMGEO.DDS",
+		buildtime = 13100,
+		canrepeat = false,
+		collisionvolumeoffsets = "0 -15 0",
+		collisionvolumescales = "67 52 67",
+		collisionvolumetype = "CylY",
+		corpse = "DEAD",
+		energycost = 13000,
+		energymake = 300,
+		energystorage = 1000,
+		explodeas = "geo",
+		footprintx = 5,
+		footprintz = 5,
+		health = 2050,
+		idleautoheal = 5,
+		idletime = 1800,
+		maxacc = 0,
+		maxdec = 0,
+		maxslope = 15,
+		maxwaterdepth = 0,
+		metalcost = 560,
+		objectname = "Units/ARMGEO.s3o",
+		script = "Units/ARMGEO.cob",
+		seismicsignature = 0,
+		selfdestructas = "geo",
+		sightdistance = 273,
+		yardmap = "ooooo ogggo ogggo ogggo ooooo",
+		customparams = {
+			geothermal = 1,
+			model_author = "Cremuss",
+			normaltex = "unittextures/Arm_normal.dds",
+			removestop = true,
+			removewait = true,
+			subfolder = "ArmBuildings/LandEconomy",
+			unitgroup = "energy",
+		},
+		featuredefs = {
+			dead = {
+				blocking = true,
+				category = "corpses",
+				collisionvolumeoffsets = "0 -15 0",
+				collisionvolumescales = "67 52 67",
+				collisionvolumetype = "CylY",
+				damage = 1100,
+				featuredead = "HEAP",
+				footprintx = 5,
+				footprintz = 5,
+				height = 20,
+				metal = 300,
+				object = "Units/armgeo_dead.s3o",
+				reclaimable = true,
+			},
+			heap = {
+				blocking = false,
+				category = "heaps",
+				damage = 550,
+				footprintx = 5,
+				footprintz = 5,
+				height = 4,
+				metal = 120,
+				object = "Units/arm5X5A.s3o",
+				reclaimable = true,
+				resurrectable = 0,
+			},
+		},
+	},
+}